@@ -1,24 +1,35 @@
 use indexmap::IndexMap;
-use serde::{Serialize, Serializer};
-use strum_macros::Display;
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    ser::{SerializeMap, SerializeSeq},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{fmt, str::FromStr};
+use strum_macros::{Display, EnumString};
 
+/// Prefix marking `NestedValue::Reference` in serialized form, so it can be
+/// told apart from a plain `NestedValue::Value` when reading the AST back
+const REFERENCE_PREFIX: &str = "refs:";
 
-#[derive(Debug, PartialEq, Serialize)]
+/// Prefix escaping plain values which would otherwise read back as references
+/// or escaped values
+const VALUE_ESCAPE: char = '\\';
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct OCAAst {
     pub version: String,
     pub commands: Vec<Command>,
 }
 
-#[derive(Debug, PartialEq, Serialize, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(try_from = "RawCommand", into = "RawCommand")]
 pub struct Command {
-    #[serde(rename = "type")]
     pub kind: CommandType,
     pub object_kind: ObjectKind,
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub content: Option<Content>,
 }
 
-#[derive(Debug, PartialEq, Serialize, Clone)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub enum CommandType {
     Add,
     Remove,
@@ -33,29 +44,38 @@ pub enum ObjectKind {
     Overlay(OverlayType),
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+/// Type of capture base attribute
+///
+/// Parsed from and displayed as its OCA name, e.g. `Text` or `Array[Text]`.
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Display, EnumString, Clone, Copy)]
 pub enum AttributeType {
     Boolean,
     #[serde(rename = "Array[Boolean]")]
+    #[strum(serialize = "Array[Boolean]")]
     ArrayBoolean,
     Binary,
     #[serde(rename = "Array[Binary]")]
+    #[strum(serialize = "Array[Binary]")]
     ArrayBinary,
     Text,
     #[serde(rename = "Array[Text]")]
+    #[strum(serialize = "Array[Text]")]
     ArrayText,
     Numeric,
     #[serde(rename = "Array[Numeric]")]
+    #[strum(serialize = "Array[Numeric]")]
     ArrayNumeric,
     DateTime,
     #[serde(rename = "Array[DateTime]")]
+    #[strum(serialize = "Array[DateTime]")]
     ArrayDateTime,
     Reference,
     #[serde(rename = "Array[Reference]")]
+    #[strum(serialize = "Array[Reference]")]
     ArrayReference,
 }
 
-#[derive(Debug, PartialEq, Serialize, Display, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Display, EnumString, Clone)]
 pub enum OverlayType {
     Label,
     Information,
@@ -78,16 +98,85 @@ pub enum OverlayType {
     Sensitivity,
 }
 
-#[derive(Debug, PartialEq, Serialize, Clone)]
-pub struct Content {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub attributes: Option<IndexMap<String, NestedValue>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub properties: Option<IndexMap<String, NestedValue>>,
+/// Content of command, shaped according to the object it operates on
+///
+/// In REMOVE commands only the keys are meaningful and values are left empty.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Content {
+    CaptureBase(CaptureBaseContent),
+    Bundle(BundleContent),
+    Meta(MetaContent),
+    Label(LabelContent),
+    Information(InformationContent),
+    CharacterEncoding(CharacterEncodingContent),
+    Format(FormatContent),
+    Unit(UnitContent),
+    /// Overlays without dedicated content model
+    Overlay(OverlayContent),
+}
+
+/// Capture base content
+///
+/// Attribute types are `None` for attributes listed in REMOVE commands.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CaptureBaseContent {
+    pub attributes: IndexMap<String, Option<AttributeType>>,
+    pub classification: Option<String>,
+    pub flagged_attributes: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct BundleContent {
+    pub said: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MetaContent {
+    pub lang: String,
+    pub properties: IndexMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct LabelContent {
+    pub lang: String,
+    pub attributes: IndexMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct InformationContent {
+    pub lang: String,
+    pub attributes: IndexMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CharacterEncodingContent {
+    pub attributes: IndexMap<String, String>,
+    pub default_encoding: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct FormatContent {
+    pub attributes: IndexMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct UnitContent {
+    pub system: String,
+    pub attributes: IndexMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct OverlayContent {
+    pub attributes: IndexMap<String, NestedValue>,
+    pub properties: IndexMap<String, NestedValue>,
 }
 
-#[derive(Debug, PartialEq, Serialize, Clone)]
-#[serde(untagged)]
+/// Value of an attribute or property
+///
+/// Serialized untagged, except for `Reference` which is written as a string
+/// prefixed with `refs:`. A plain value starting with `refs:` or `\` is
+/// written with a `\` in front, so it is read back as a value.
+#[derive(Debug, PartialEq, Clone)]
 pub enum NestedValue {
     Value(String),
     Object(IndexMap<String, NestedValue>),
@@ -120,6 +209,393 @@ impl Serialize for ObjectKind {
     }
 }
 
+/// Serialized form of command, with content kept as loose attributes and
+/// properties maps
+#[derive(Serialize, Deserialize)]
+struct RawCommand {
+    #[serde(rename = "type")]
+    kind: CommandType,
+    object_kind: ObjectKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<RawContent>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct RawContent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attributes: Option<IndexMap<String, NestedValue>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    properties: Option<IndexMap<String, NestedValue>>,
+}
+
+impl From<Command> for RawCommand {
+    fn from(command: Command) -> Self {
+        RawCommand {
+            kind: command.kind,
+            object_kind: command.object_kind,
+            content: command.content.map(RawContent::from),
+        }
+    }
+}
+
+impl TryFrom<RawCommand> for Command {
+    type Error = String;
+
+    fn try_from(raw: RawCommand) -> Result<Self, Self::Error> {
+        let content = match raw.content {
+            Some(content) => Some(content.into_content(&raw.object_kind)?),
+            None => None,
+        };
+        Ok(Command {
+            kind: raw.kind,
+            object_kind: raw.object_kind,
+            content,
+        })
+    }
+}
+
+impl RawContent {
+    fn with_attributes(mut self, attributes: IndexMap<String, NestedValue>) -> Self {
+        if !attributes.is_empty() {
+            self.attributes = Some(attributes);
+        }
+        self
+    }
+
+    fn with_properties(mut self, properties: IndexMap<String, NestedValue>) -> Self {
+        if !properties.is_empty() {
+            self.properties = Some(properties);
+        }
+        self
+    }
+
+    /// Overlay content, serialized with both maps even if empty
+    fn overlay(
+        attributes: IndexMap<String, NestedValue>,
+        properties: IndexMap<String, NestedValue>,
+    ) -> Self {
+        RawContent {
+            attributes: Some(attributes),
+            properties: Some(properties),
+        }
+    }
+
+    fn into_content(self, object_kind: &ObjectKind) -> Result<Content, String> {
+        let mut attributes = self.attributes.unwrap_or_default();
+        let mut properties = self.properties.unwrap_or_default();
+
+        let content = match object_kind {
+            ObjectKind::CaptureBase => {
+                let classification = take_string(&mut properties, "classification")?;
+                let flagged_attributes = match properties.shift_remove("flagged_attributes") {
+                    Some(NestedValue::Array(flagged)) => flagged
+                        .into_iter()
+                        .map(|attr| match attr {
+                            NestedValue::Value(attr) => Ok(attr),
+                            _ => Err("flagged attribute must be a string".to_string()),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?,
+                    Some(_) => return Err("flagged_attributes must be an array".to_string()),
+                    None => Vec::new(),
+                };
+                Content::CaptureBase(CaptureBaseContent {
+                    attributes: into_attribute_types(std::mem::take(&mut attributes))?,
+                    classification,
+                    flagged_attributes,
+                })
+            }
+            ObjectKind::OCABundle => Content::Bundle(BundleContent {
+                said: require_string(&mut properties, "said")?,
+            }),
+            ObjectKind::Overlay(OverlayType::Meta) => Content::Meta(MetaContent {
+                lang: require_string(&mut properties, "lang")?,
+                properties: into_strings(std::mem::take(&mut properties))?,
+            }),
+            ObjectKind::Overlay(OverlayType::Label) => Content::Label(LabelContent {
+                lang: require_string(&mut properties, "lang")?,
+                attributes: into_strings(std::mem::take(&mut attributes))?,
+            }),
+            ObjectKind::Overlay(OverlayType::Information) => {
+                Content::Information(InformationContent {
+                    lang: require_string(&mut properties, "lang")?,
+                    attributes: into_strings(std::mem::take(&mut attributes))?,
+                })
+            }
+            ObjectKind::Overlay(OverlayType::CharacterEncoding) => {
+                Content::CharacterEncoding(CharacterEncodingContent {
+                    default_encoding: take_string(&mut properties, "default_encoding")?,
+                    attributes: into_strings(std::mem::take(&mut attributes))?,
+                })
+            }
+            ObjectKind::Overlay(OverlayType::Format) => Content::Format(FormatContent {
+                attributes: into_strings(std::mem::take(&mut attributes))?,
+            }),
+            ObjectKind::Overlay(OverlayType::Unit) => Content::Unit(UnitContent {
+                system: require_string(&mut properties, "unit_system")?,
+                attributes: into_strings(std::mem::take(&mut attributes))?,
+            }),
+            ObjectKind::Overlay(_) => Content::Overlay(OverlayContent {
+                attributes: std::mem::take(&mut attributes),
+                properties: std::mem::take(&mut properties),
+            }),
+        };
+
+        if let Some(key) = attributes.keys().chain(properties.keys()).next() {
+            return Err(format!("unexpected key {:?} in {} content", key, object_kind));
+        }
+        Ok(content)
+    }
+}
+
+impl From<Content> for RawContent {
+    fn from(content: Content) -> Self {
+        let raw = RawContent::default();
+        match content {
+            Content::CaptureBase(content) => {
+                let mut properties = IndexMap::new();
+                if let Some(classification) = content.classification {
+                    properties.insert(
+                        "classification".to_string(),
+                        NestedValue::Value(classification),
+                    );
+                }
+                if !content.flagged_attributes.is_empty() {
+                    properties.insert(
+                        "flagged_attributes".to_string(),
+                        NestedValue::Array(
+                            content
+                                .flagged_attributes
+                                .into_iter()
+                                .map(NestedValue::Value)
+                                .collect(),
+                        ),
+                    );
+                }
+                raw.with_attributes(from_attribute_types(content.attributes))
+                    .with_properties(properties)
+            }
+            Content::Bundle(content) => {
+                raw.with_properties(string_map([("said", content.said)]))
+            }
+            Content::Meta(content) => {
+                let mut properties = string_map([("lang", content.lang)]);
+                properties.extend(from_strings(content.properties));
+                RawContent::overlay(IndexMap::new(), properties)
+            }
+            Content::Label(content) => RawContent::overlay(
+                from_strings(content.attributes),
+                string_map([("lang", content.lang)]),
+            ),
+            Content::Information(content) => RawContent::overlay(
+                from_strings(content.attributes),
+                string_map([("lang", content.lang)]),
+            ),
+            Content::CharacterEncoding(content) => RawContent::overlay(
+                from_strings(content.attributes),
+                string_map(
+                    content
+                        .default_encoding
+                        .map(|encoding| ("default_encoding", encoding)),
+                ),
+            ),
+            Content::Format(content) => {
+                RawContent::overlay(from_strings(content.attributes), IndexMap::new())
+            }
+            Content::Unit(content) => RawContent::overlay(
+                from_strings(content.attributes),
+                string_map([("unit_system", content.system)]),
+            ),
+            Content::Overlay(content) => {
+                RawContent::overlay(content.attributes, content.properties)
+            }
+        }
+    }
+}
+
+fn string_map<'a>(
+    entries: impl IntoIterator<Item = (&'a str, String)>,
+) -> IndexMap<String, NestedValue> {
+    entries
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), NestedValue::Value(value)))
+        .collect()
+}
+
+fn from_strings(map: IndexMap<String, String>) -> IndexMap<String, NestedValue> {
+    map.into_iter()
+        .map(|(key, value)| (key, NestedValue::Value(value)))
+        .collect()
+}
+
+fn into_strings(map: IndexMap<String, NestedValue>) -> Result<IndexMap<String, String>, String> {
+    map.into_iter()
+        .map(|(key, value)| match value {
+            NestedValue::Value(value) => Ok((key, value)),
+            _ => Err(format!("value of {:?} must be a string", key)),
+        })
+        .collect()
+}
+
+fn from_attribute_types(
+    map: IndexMap<String, Option<AttributeType>>,
+) -> IndexMap<String, NestedValue> {
+    map.into_iter()
+        .map(|(key, attr_type)| {
+            let attr_type = attr_type.map(|t| t.to_string()).unwrap_or_default();
+            (key, NestedValue::Value(attr_type))
+        })
+        .collect()
+}
+
+fn into_attribute_types(
+    map: IndexMap<String, NestedValue>,
+) -> Result<IndexMap<String, Option<AttributeType>>, String> {
+    into_strings(map)?
+        .into_iter()
+        .map(|(key, attr_type)| match attr_type.as_str() {
+            "" => Ok((key, None)),
+            _ => AttributeType::from_str(&attr_type)
+                .map(|attr_type| (key, Some(attr_type)))
+                .map_err(|_| format!("invalid attribute type {:?}", attr_type)),
+        })
+        .collect()
+}
+
+fn take_string(
+    map: &mut IndexMap<String, NestedValue>,
+    key: &str,
+) -> Result<Option<String>, String> {
+    match map.shift_remove(key) {
+        Some(NestedValue::Value(value)) => Ok(Some(value)),
+        Some(_) => Err(format!("value of {:?} must be a string", key)),
+        None => Ok(None),
+    }
+}
+
+fn require_string(map: &mut IndexMap<String, NestedValue>, key: &str) -> Result<String, String> {
+    take_string(map, key)?.ok_or_else(|| format!("missing {:?} property", key))
+}
+
+impl fmt::Display for ObjectKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjectKind::CaptureBase => write!(f, "CaptureBase"),
+            ObjectKind::OCABundle => write!(f, "OCABundle"),
+            ObjectKind::Overlay(overlay_type) => write!(f, "{}", overlay_type),
+        }
+    }
+}
+
+impl FromStr for ObjectKind {
+    type Err = strum::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "CaptureBase" => Ok(ObjectKind::CaptureBase),
+            "OCABundle" => Ok(ObjectKind::OCABundle),
+            _ => OverlayType::from_str(s).map(ObjectKind::Overlay),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ObjectKind {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let object_kind = String::deserialize(deserializer)?;
+        ObjectKind::from_str(&object_kind)
+            .map_err(|_| de::Error::custom(format!("unknown object kind: {}", object_kind)))
+    }
+}
+
+impl Serialize for NestedValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            NestedValue::Value(value)
+                if value.starts_with(REFERENCE_PREFIX) || value.starts_with(VALUE_ESCAPE) =>
+            {
+                serializer.serialize_str(&format!("{}{}", VALUE_ESCAPE, value))
+            }
+            NestedValue::Value(value) => serializer.serialize_str(value),
+            NestedValue::Reference(reference) => {
+                serializer.serialize_str(&format!("{}{}", REFERENCE_PREFIX, reference))
+            }
+            NestedValue::Object(object) => {
+                let mut map = serializer.serialize_map(Some(object.len()))?;
+                for (key, value) in object {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+            NestedValue::Array(array) => {
+                let mut seq = serializer.serialize_seq(Some(array.len()))?;
+                for value in array {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for NestedValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct NestedValueVisitor;
+
+        impl<'de> Visitor<'de> for NestedValueVisitor {
+            type Value = NestedValue;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a string, an array or an object")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                if let Some(value) = value.strip_prefix(VALUE_ESCAPE) {
+                    return Ok(NestedValue::Value(value.to_string()));
+                }
+                match value.strip_prefix(REFERENCE_PREFIX) {
+                    Some(reference) => Ok(NestedValue::Reference(reference.to_string())),
+                    None => Ok(NestedValue::Value(value.to_string())),
+                }
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut array = Vec::new();
+                while let Some(value) = seq.next_element()? {
+                    array.push(value);
+                }
+                Ok(NestedValue::Array(array))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut object = IndexMap::new();
+                while let Some((key, value)) = map.next_entry()? {
+                    object.insert(key, value);
+                }
+                Ok(NestedValue::Object(object))
+            }
+        }
+
+        deserializer.deserialize_any(NestedValueVisitor)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -127,20 +603,16 @@ mod tests {
     #[test]
     fn test_ocaast_serialize() {
         let mut attributes = IndexMap::new();
-        let mut properties = IndexMap::new();
-        let mut person = IndexMap::new();
-        person.insert("name".to_string(), NestedValue::Value("Text".to_string()));
-
-        attributes.insert("test".to_string(), NestedValue::Value("test".to_string()));
-        attributes.insert("person".to_string(), NestedValue::Object(person));
-        properties.insert("test".to_string(), NestedValue::Value("test".to_string()));
+        attributes.insert("test".to_string(), Some(AttributeType::Text));
+        attributes.insert("list".to_string(), Some(AttributeType::ArrayNumeric));
         let command = Command {
             kind: CommandType::Add,
             object_kind: ObjectKind::CaptureBase,
-            content: Some(Content {
-                attributes: Some(attributes),
-                properties: Some(properties),
-            }),
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes,
+                classification: Some("test".to_string()),
+                flagged_attributes: vec![],
+            })),
         };
 
         let mut ocaast = OCAAst::new();
@@ -148,7 +620,154 @@ mod tests {
         let serialized = serde_json::to_string(&ocaast).unwrap();
         assert_eq!(
             serialized,
-            r#"{"version":"1.0","commands":[{"type":"Add","object_kind":"CaptureBase","content":{"attributes":{"test":"test","person":{"name":"Text"}},"properties":{"test":"test"}}}]}"#
+            r#"{"version":"1.0.0","commands":[{"type":"Add","object_kind":"CaptureBase","content":{"attributes":{"test":"Text","list":"Array[Numeric]"},"properties":{"classification":"test"}}}]}"#
+        );
+    }
+
+    #[test]
+    fn test_ocaast_deserialize() {
+        let mut attributes = IndexMap::new();
+        attributes.insert("name".to_string(), Some(AttributeType::Text));
+        attributes.insert("passport".to_string(), Some(AttributeType::Reference));
+        let mut entries = IndexMap::new();
+        entries.insert(
+            "passport".to_string(),
+            NestedValue::Reference("E2oRZ5zEKxTfTdECW-v2Q7bM_H0OD0ko7IcCwdo_u9co".to_string()),
+        );
+        entries.insert(
+            "codes".to_string(),
+            NestedValue::Array(vec![
+                NestedValue::Value("a".to_string()),
+                NestedValue::Value("b".to_string()),
+            ]),
+        );
+
+        let mut ocaast = OCAAst::new();
+        ocaast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::CaptureBase,
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes,
+                classification: None,
+                flagged_attributes: vec!["name".to_string()],
+            })),
+        });
+        ocaast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::CharacterEncoding),
+            content: Some(Content::CharacterEncoding(CharacterEncodingContent {
+                attributes: IndexMap::new(),
+                default_encoding: Some("utf-8".to_string()),
+            })),
+        });
+        ocaast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::EntryCode),
+            content: Some(Content::Overlay(OverlayContent {
+                attributes: entries,
+                properties: IndexMap::new(),
+            })),
+        });
+        ocaast.commands.push(Command {
+            kind: CommandType::From,
+            object_kind: ObjectKind::OCABundle,
+            content: None,
+        });
+
+        let serialized = serde_json::to_string(&ocaast).unwrap();
+        assert!(serialized.contains(r#""passport":"refs:E2oRZ5zEKxTfTdECW-v2Q7bM_H0OD0ko7IcCwdo_u9co""#));
+        let deserialized: OCAAst = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, ocaast);
+
+        let unknown_kind = r#"{"version":"1.0.0","commands":[{"type":"Add","object_kind":"Unknown"}]}"#;
+        assert!(serde_json::from_str::<OCAAst>(unknown_kind).is_err());
+    }
+
+    #[test]
+    fn test_nested_value_escaping() {
+        let values = vec![
+            NestedValue::Value("refs:not a reference".to_string()),
+            NestedValue::Value("\\refs:".to_string()),
+            NestedValue::Value("plain".to_string()),
+            NestedValue::Reference("E2oRZ5zEKxTfTdECW-v2Q7bM_H0OD0ko7IcCwdo_u9co".to_string()),
+        ];
+        let serialized = serde_json::to_string(&values).unwrap();
+        assert_eq!(
+            serialized,
+            r#"["\\refs:not a reference","\\\\refs:","plain","refs:E2oRZ5zEKxTfTdECW-v2Q7bM_H0OD0ko7IcCwdo_u9co"]"#
+        );
+        let deserialized: Vec<NestedValue> = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, values);
+    }
+
+    #[test]
+    fn test_typed_content_deserialize() {
+        let serialized = r#"{"version":"1.0.0","commands":[
+            {"type":"Add","object_kind":"Label","content":{"attributes":{"name":"Name"},"properties":{"lang":"en"}}},
+            {"type":"Add","object_kind":"Unit","content":{"attributes":{"height":"cm"},"properties":{"unit_system":"si"}}},
+            {"type":"Add","object_kind":"Meta","content":{"attributes":{},"properties":{"lang":"en","name":"Passport"}}},
+            {"type":"Add","object_kind":"Entry","content":{"attributes":{"sex":{"M":"Male"}},"properties":{"lang":"en"}}}
+        ]}"#;
+        let ocaast: OCAAst = serde_json::from_str(serialized).unwrap();
+        let contents = ocaast
+            .commands
+            .into_iter()
+            .map(|command| command.content.unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            contents[0],
+            Content::Label(LabelContent {
+                lang: "en".to_string(),
+                attributes: IndexMap::from([("name".to_string(), "Name".to_string())]),
+            })
+        );
+        assert_eq!(
+            contents[1],
+            Content::Unit(UnitContent {
+                system: "si".to_string(),
+                attributes: IndexMap::from([("height".to_string(), "cm".to_string())]),
+            })
+        );
+        assert_eq!(
+            contents[2],
+            Content::Meta(MetaContent {
+                lang: "en".to_string(),
+                properties: IndexMap::from([("name".to_string(), "Passport".to_string())]),
+            })
+        );
+        assert!(matches!(contents[3], Content::Overlay(_)));
+
+        let missing_lang = r#"{"version":"1.0.0","commands":[{"type":"Add","object_kind":"Label","content":{"attributes":{"name":"Name"}}}]}"#;
+        assert!(serde_json::from_str::<OCAAst>(missing_lang).is_err());
+        let unexpected_key = r#"{"version":"1.0.0","commands":[{"type":"Add","object_kind":"Format","content":{"properties":{"lang":"en"}}}]}"#;
+        assert!(serde_json::from_str::<OCAAst>(unexpected_key).is_err());
+        let invalid_type = r#"{"version":"1.0.0","commands":[{"type":"Add","object_kind":"CaptureBase","content":{"attributes":{"name":"Txt"}}}]}"#;
+        assert!(serde_json::from_str::<OCAAst>(invalid_type).is_err());
+    }
+
+    #[test]
+    fn test_ocaast_example_round_trip() {
+        let example = include_str!("../fixtures/example.ocafile.ast");
+        let ocaast: OCAAst = serde_json::from_str(example).unwrap();
+        assert_eq!(
+            serde_json::to_value(&ocaast).unwrap(),
+            serde_json::from_str::<serde_json::Value>(example).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_attribute_type_from_str() {
+        assert_eq!(AttributeType::from_str("Text"), Ok(AttributeType::Text));
+        assert_eq!(
+            AttributeType::from_str("Array[DateTime]"),
+            Ok(AttributeType::ArrayDateTime)
+        );
+        assert!(AttributeType::from_str("Array[Random]").is_err());
+        assert_eq!(AttributeType::ArrayBinary.to_string(), "Array[Binary]");
+        assert_eq!(
+            serde_json::from_str::<AttributeType>(r#""Array[Reference]""#).unwrap(),
+            AttributeType::ArrayReference
         );
     }
 }
@@ -0,0 +1,277 @@
+//! Language coverage of translatable overlays
+//!
+//! For every translatable overlay type the keys present in any language are
+//! expected in all languages, so a label given in `en` but not in `de` is
+//! reported as missing from `de`. Labels and information are also expected
+//! for every attribute of the capture base.
+
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::fmt;
+
+use crate::{
+    ast::{Content, OverlayType},
+    errors::Error,
+    report::Severity,
+    state::{attribute_keys, property_keys, OCAState},
+    validator::Rule,
+};
+
+/// Overlay types which describe every attribute of capture base
+const PER_ATTRIBUTE: [OverlayType; 2] = [OverlayType::Label, OverlayType::Information];
+
+/// Overlay types whose content is given per language
+const TRANSLATABLE: [OverlayType; 4] = [
+    OverlayType::Label,
+    OverlayType::Information,
+    OverlayType::Meta,
+    OverlayType::Entry,
+];
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct CoverageGap {
+    pub overlay_type: OverlayType,
+    pub lang: String,
+    /// Attributes, or properties for Meta, missing in the language
+    pub missing: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default, Serialize)]
+pub struct CoverageReport {
+    /// Languages seen in overlays together with the required ones
+    pub languages: Vec<String>,
+    pub gaps: Vec<CoverageGap>,
+}
+
+impl CoverageReport {
+    pub fn is_complete(&self) -> bool {
+        self.gaps.is_empty()
+    }
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for gap in &self.gaps {
+            writeln!(
+                f,
+                "{} ({}): missing {}",
+                gap.overlay_type,
+                gap.lang,
+                gap.missing.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Find keys missing in each language of translatable overlays
+///
+/// Languages in `required_languages` are checked even if no overlay uses
+/// them yet.
+pub fn coverage(state: &OCAState, required_languages: &[String]) -> CoverageReport {
+    let mut languages: Vec<String> = state.languages().into_iter().map(str::to_string).collect();
+    for lang in required_languages {
+        if !languages.contains(lang) {
+            languages.push(lang.clone());
+        }
+    }
+
+    let mut gaps = Vec::new();
+    for overlay_type in &TRANSLATABLE {
+        let overlays: IndexMap<&str, Vec<&str>> = state
+            .overlays
+            .iter()
+            .filter(|(key, _)| key.overlay_type == *overlay_type)
+            .filter_map(|(key, content)| Some((key.lang.as_deref()?, translated_keys(content))))
+            .collect();
+        if overlays.is_empty() {
+            continue;
+        }
+        let mut expected: Vec<&str> = Vec::new();
+        if PER_ATTRIBUTE.contains(overlay_type) {
+            expected.extend(state.attributes.keys().map(String::as_str));
+        }
+        for key in overlays.values().flatten() {
+            if !expected.contains(key) {
+                expected.push(key);
+            }
+        }
+        for lang in &languages {
+            let present = overlays.get(lang.as_str());
+            let missing: Vec<String> = expected
+                .iter()
+                .filter(|key| !present.is_some_and(|present| present.contains(key)))
+                .map(|key| key.to_string())
+                .collect();
+            if !missing.is_empty() {
+                gaps.push(CoverageGap {
+                    overlay_type: overlay_type.clone(),
+                    lang: lang.clone(),
+                    missing,
+                });
+            }
+        }
+    }
+
+    CoverageReport { languages, gaps }
+}
+
+fn translated_keys(content: &Content) -> Vec<&str> {
+    match content {
+        Content::Meta(_) => property_keys(content),
+        _ => attribute_keys(content),
+    }
+}
+
+/// Validation rule reporting gaps in language coverage
+///
+/// Configured with `{"languages": ["en", "pl"]}` to require given languages.
+#[derive(Debug, Default)]
+pub struct LanguageCoverageRule {
+    pub required_languages: Vec<String>,
+}
+
+impl Rule for LanguageCoverageRule {
+    fn id(&self) -> &str {
+        "language-coverage"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn configure(&mut self, options: &serde_json::Value) -> Result<(), String> {
+        let languages = options["languages"]
+            .as_array()
+            .ok_or_else(|| "expected \"languages\" list".to_string())?;
+        self.required_languages = languages
+            .iter()
+            .map(|lang| {
+                lang.as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| format!("invalid language {}", lang))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    fn check_state(&self, state: &OCAState) -> Vec<Error> {
+        coverage(state, &self.required_languages)
+            .gaps
+            .into_iter()
+            .map(|gap| Error::MissingTranslations {
+                overlay_type: gap.overlay_type.to_string(),
+                lang: gap.lang,
+                missing: gap.missing,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{
+        AttributeType, CaptureBaseContent, Command, CommandType, LabelContent, MetaContent,
+        OCAAst, ObjectKind,
+    };
+    use crate::validator::{OCAValidator, Validator};
+    use indexmap::indexmap;
+
+    fn example_ast() -> OCAAst {
+        let mut ast = OCAAst::new();
+        ast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::CaptureBase,
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes: indexmap! {
+                    "name".to_string() => Some(AttributeType::Text),
+                    "age".to_string() => Some(AttributeType::Numeric),
+                },
+                ..Default::default()
+            })),
+        });
+        for (lang, attributes) in [
+            ("en", vec![("name", "Name"), ("age", "Age")]),
+            ("pl", vec![("name", "Imię")]),
+        ] {
+            ast.commands.push(Command {
+                kind: CommandType::Add,
+                object_kind: ObjectKind::Overlay(OverlayType::Label),
+                content: Some(Content::Label(LabelContent {
+                    lang: lang.to_string(),
+                    attributes: attributes
+                        .into_iter()
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect(),
+                })),
+            });
+        }
+        ast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::Meta),
+            content: Some(Content::Meta(MetaContent {
+                lang: "pl".to_string(),
+                properties: indexmap! { "name".to_string() => "Paszport".to_string() },
+            })),
+        });
+        ast
+    }
+
+    #[test]
+    fn test_coverage() {
+        let state = OCAState::from_ast(&example_ast());
+        let report = coverage(&state, &["de".to_string()]);
+
+        assert_eq!(report.languages, vec!["en", "pl", "de"]);
+        assert!(!report.is_complete());
+        assert_eq!(
+            report.to_string(),
+            "Label (pl): missing age\n\
+             Label (de): missing name, age\n\
+             Meta (en): missing name\n\
+             Meta (de): missing name\n"
+        );
+    }
+
+    #[test]
+    fn test_coverage_of_attributes() {
+        let mut ast = example_ast();
+        ast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::CaptureBase,
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes: indexmap! { "photo".to_string() => Some(AttributeType::Binary) },
+                ..Default::default()
+            })),
+        });
+        let state = OCAState::from_ast(&ast);
+        let report = coverage(&state, &["de".to_string()]);
+
+        // photo has no label in any language
+        assert_eq!(
+            report.to_string(),
+            "Label (en): missing photo\n\
+             Label (pl): missing age, photo\n\
+             Label (de): missing name, age, photo\n\
+             Meta (en): missing name\n\
+             Meta (de): missing name\n"
+        );
+    }
+
+    #[test]
+    fn test_language_coverage_rule() {
+        let mut validator = OCAValidator::new();
+        validator
+            .configure("language-coverage", &serde_json::json!({ "languages": ["pl"] }))
+            .unwrap();
+        let report = validator.validate_ast(&example_ast());
+
+        assert!(report.is_valid());
+        assert_eq!(
+            report.to_string(),
+            "warning[language-coverage]: Label overlay in pl is missing \"age\"\n\
+             warning[language-coverage]: Meta overlay in en is missing \"name\"\n"
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("Missing version of OCA AST")]
+    MissingVersion(),
+
+    #[error("Unsupported version of OCA AST: {0}")]
+    InvalidVersion(String),
+
+    #[error("Attribute {attribute:?} does not exist")]
+    AttributeNotFound { attribute: String },
+
+    #[error("Attribute {attribute:?} already exists")]
+    AttributeAlreadyExists { attribute: String },
+
+    #[error("Attribute {attribute:?} has no valid type")]
+    InvalidAttributeType { attribute: String },
+
+    #[error("Classification is not set")]
+    ClassificationNotFound,
+
+    #[error("{overlay} overlay does not exist")]
+    OverlayNotFound { overlay: String },
+
+    #[error("{key:?} does not exist in {overlay} overlay")]
+    OverlayKeyNotFound { overlay: String, key: String },
+
+    #[error(
+        "{overlay} overlay refers to attribute {attribute:?} which is not in capture base{}",
+        format_suggestion(.suggestion)
+    )]
+    UnknownAttribute {
+        overlay: String,
+        attribute: String,
+        suggestion: Option<String>,
+    },
+
+    #[error("Invalid format {format:?} of attribute {attribute:?}: {reason}")]
+    InvalidFormat {
+        attribute: String,
+        format: String,
+        reason: String,
+    },
+
+    #[error("Unknown character encoding {encoding:?} of {key:?}")]
+    UnknownCharacterEncoding { key: String, encoding: String },
+
+    #[error("Unknown unit system {0:?}")]
+    UnknownUnitSystem(String),
+
+    #[error("Unknown unit {unit:?} of attribute {attribute:?} in {system} unit system")]
+    UnknownUnit {
+        attribute: String,
+        unit: String,
+        system: String,
+    },
+
+    #[error("Attribute {attribute:?} of type {attr_type} can't have unit")]
+    UnitOnNonNumeric {
+        attribute: String,
+        attr_type: String,
+    },
+
+    #[error("{key:?} is reserved and can't be a property of {overlay} overlay")]
+    ReservedProperty { overlay: String, key: String },
+
+    #[error("Unknown validation rule {0:?}")]
+    UnknownRule(String),
+
+    #[error("Invalid configuration of rule {rule:?}: {reason}")]
+    InvalidRuleConfiguration { rule: String, reason: String },
+
+    #[error("{overlay_type} overlay in {lang} is missing {}", format_keys(.missing))]
+    MissingTranslations {
+        overlay_type: String,
+        lang: String,
+        missing: Vec<String>,
+    },
+
+    /// Finding of rule registered outside of this crate
+    #[error("{0}")]
+    Rule(String),
+}
+
+fn format_suggestion(suggestion: &Option<String>) -> String {
+    match suggestion {
+        Some(suggestion) => format!(", did you mean {:?}?", suggestion),
+        None => String::new(),
+    }
+}
+
+fn format_keys(keys: &[String]) -> String {
+    keys.iter()
+        .map(|key| format!("{:?}", key))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
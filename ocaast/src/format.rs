@@ -0,0 +1,122 @@
+//! Checks of Format overlay values
+//!
+//! Meaning of format depends on type of attribute: it is a regular
+//! expression for Text and Numeric, a date pattern like `YYYY-MM-DD` for
+//! DateTime and a MIME type for Binary.
+
+use regex::Regex;
+
+use crate::ast::AttributeType;
+
+/// Tokens of date and time patterns, longest first so that `YYYY` is not
+/// read as two `YY`
+const DATE_TIME_TOKENS: [&str; 10] = ["YYYY", "SSS", "YY", "MM", "DD", "hh", "HH", "mm", "ss", "Z"];
+
+const DATE_TIME_SEPARATORS: [char; 7] = ['-', '/', ':', '.', ',', ' ', 'T'];
+
+/// Top level MIME types, as registered by IANA
+const MIME_TYPES: [&str; 10] = [
+    "application",
+    "audio",
+    "font",
+    "image",
+    "message",
+    "model",
+    "multipart",
+    "text",
+    "video",
+    "example",
+];
+
+/// Check that format is meaningful for attribute of given type
+pub fn check_format(attr_type: &AttributeType, format: &str) -> Result<(), String> {
+    match attr_type {
+        AttributeType::Text
+        | AttributeType::ArrayText
+        | AttributeType::Numeric
+        | AttributeType::ArrayNumeric => check_regex(format),
+        AttributeType::DateTime | AttributeType::ArrayDateTime => check_date_time_pattern(format),
+        AttributeType::Binary | AttributeType::ArrayBinary => check_mime_type(format),
+        AttributeType::Boolean
+        | AttributeType::ArrayBoolean
+        | AttributeType::Reference
+        | AttributeType::ArrayReference => {
+            Err(format!("{} attributes can't have format", attr_type))
+        }
+    }
+}
+
+fn check_regex(format: &str) -> Result<(), String> {
+    Regex::new(format)
+        .map(|_| ())
+        .map_err(|e| format!("invalid regular expression: {}", e))
+}
+
+fn check_date_time_pattern(format: &str) -> Result<(), String> {
+    let mut rest = format;
+    let mut has_token = false;
+    while !rest.is_empty() {
+        if let Some(token) = DATE_TIME_TOKENS
+            .iter()
+            .find(|token| rest.starts_with(*token))
+        {
+            rest = &rest[token.len()..];
+            has_token = true;
+        } else {
+            let c = rest.chars().next().unwrap();
+            if !DATE_TIME_SEPARATORS.contains(&c) {
+                return Err(format!("unexpected {:?} in date pattern", c));
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    if has_token {
+        Ok(())
+    } else {
+        Err("date pattern has no date or time fields".to_string())
+    }
+}
+
+fn check_mime_type(format: &str) -> Result<(), String> {
+    let (top_level, subtype) = format
+        .split_once('/')
+        .ok_or_else(|| "expected MIME type like image/jpeg".to_string())?;
+    if !MIME_TYPES.contains(&top_level.to_lowercase().as_str()) {
+        return Err(format!("unknown MIME type {:?}", top_level));
+    }
+    // restricted name of RFC 6838
+    let is_restricted_name = subtype.len() <= 127
+        && subtype
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric())
+        && subtype
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c));
+    if is_restricted_name {
+        Ok(())
+    } else {
+        Err(format!("invalid MIME subtype {:?}", subtype))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_format() {
+        assert!(check_format(&AttributeType::Text, "[A-Z0-9]{9}").is_ok());
+        assert!(check_format(&AttributeType::Text, "[A-Z0-9{9}").is_err());
+        assert!(check_format(&AttributeType::DateTime, "YYYY-MM-DD").is_ok());
+        assert!(check_format(&AttributeType::DateTime, "YYYY-MM-DDThh:mm:ss.SSSZ").is_ok());
+        assert!(check_format(&AttributeType::DateTime, "DD/MM/YY").is_ok());
+        assert!(check_format(&AttributeType::DateTime, "[0-9]{4}").is_err());
+        assert!(check_format(&AttributeType::DateTime, "--").is_err());
+        assert!(check_format(&AttributeType::Binary, "image/jpeg").is_ok());
+        assert!(check_format(&AttributeType::Binary, "application/vnd.api+json").is_ok());
+        assert!(check_format(&AttributeType::Binary, "[A-Z0-9]{9}").is_err());
+        assert!(check_format(&AttributeType::Binary, "picture/jpeg").is_err());
+        assert!(check_format(&AttributeType::Boolean, "true|false").is_err());
+    }
+}
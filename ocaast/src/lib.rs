@@ -1,3 +1,10 @@
 pub mod ast;
+pub mod coverage;
+pub mod state;
 pub mod validator;
-mod errors;
\ No newline at end of file
+pub mod visit;
+pub mod errors;
+pub mod format;
+pub mod registries;
+pub mod report;
+mod suggestions;
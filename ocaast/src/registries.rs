@@ -0,0 +1,109 @@
+//! Embedded registries of values allowed in overlays
+//!
+//! Character encodings and unit system names are matched case-insensitively,
+//! units are case sensitive since e.g. `m` and `M` differ.
+
+pub const CHARACTER_ENCODINGS: &[&str] = &[
+    "utf-8",
+    "utf-16",
+    "utf-16le",
+    "utf-16be",
+    "utf-32",
+    "us-ascii",
+    "iso-8859-1",
+    "iso-8859-2",
+    "iso-8859-3",
+    "iso-8859-4",
+    "iso-8859-5",
+    "iso-8859-6",
+    "iso-8859-7",
+    "iso-8859-8",
+    "iso-8859-9",
+    "iso-8859-10",
+    "iso-8859-11",
+    "iso-8859-13",
+    "iso-8859-14",
+    "iso-8859-15",
+    "iso-8859-16",
+    "windows-1250",
+    "windows-1251",
+    "windows-1252",
+    "base64",
+    "base64url",
+    "base32",
+    "hex",
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnitSystem {
+    pub name: &'static str,
+    pub units: &'static [&'static str],
+}
+
+impl UnitSystem {
+    pub fn has_unit(&self, unit: &str) -> bool {
+        self.units.contains(&unit)
+    }
+}
+
+pub const UNIT_SYSTEMS: &[UnitSystem] = &[
+    UnitSystem {
+        name: "si",
+        units: &[
+            // base units with common multiples
+            "m", "km", "dm", "cm", "mm", "um", "nm", "g", "kg", "mg", "ug", "t", "s", "ms", "us",
+            "ns", "min", "h", "d", "A", "mA", "K", "mol", "cd",
+            // derived and accepted units
+            "m2", "km2", "cm2", "mm2", "ha", "m3", "cm3", "mm3", "l", "dl", "cl", "ml", "Hz", "kHz",
+            "MHz", "N", "Pa", "kPa", "hPa", "J", "kJ", "W", "kW", "MW", "V", "mV", "Ohm", "C",
+            "degC", "rad", "sr", "lm", "lx", "Bq", "Gy", "Sv", "kat", "m/s", "km/h", "m/s2",
+            "kg/m3", "g/l", "mg/dl", "mmol/l", "%",
+        ],
+    },
+    UnitSystem {
+        name: "imperial",
+        units: &[
+            "in", "ft", "yd", "mi", "nmi", "sq in", "sq ft", "sq yd", "acre", "sq mi", "fl oz",
+            "pt", "qt", "gal", "oz", "lb", "st", "ton", "degF", "mph", "psi", "%",
+        ],
+    },
+    UnitSystem {
+        name: "us",
+        units: &[
+            "in", "ft", "yd", "mi", "nmi", "sq in", "sq ft", "sq yd", "acre", "sq mi", "tsp",
+            "tbsp", "fl oz", "cup", "pt", "qt", "gal", "oz", "lb", "ton", "degF", "mph", "psi",
+            "%",
+        ],
+    },
+];
+
+pub fn is_character_encoding(encoding: &str) -> bool {
+    CHARACTER_ENCODINGS
+        .iter()
+        .any(|known| known.eq_ignore_ascii_case(encoding))
+}
+
+pub fn unit_system(name: &str) -> Option<&'static UnitSystem> {
+    UNIT_SYSTEMS
+        .iter()
+        .find(|system| system.name.eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registries() {
+        assert!(is_character_encoding("utf-8"));
+        assert!(is_character_encoding("UTF-8"));
+        assert!(is_character_encoding("base64"));
+        assert!(!is_character_encoding("base65"));
+
+        let si = unit_system("SI").unwrap();
+        assert!(si.has_unit("cm"));
+        assert!(!si.has_unit("CM"));
+        assert!(unit_system("imperial").unwrap().has_unit("lb"));
+        assert_eq!(unit_system("cubits"), None);
+    }
+}
@@ -0,0 +1,199 @@
+//! Results of validation
+//!
+//! Validation does not stop at the first problem, every finding is collected
+//! into `ValidationReport`, which can be printed for humans or serialized to
+//! JSON for tooling.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::errors::Error;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Info => write!(f, "info"),
+        }
+    }
+}
+
+/// Location of command in source it was parsed from
+///
+/// `start` and `end` are byte offsets, `line` and `column` are 1-based.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Finding {
+    /// Id of rule which produced the finding
+    pub rule: String,
+    pub severity: Severity,
+    /// Index of offending command, `None` for findings about whole AST
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub command: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+impl Finding {
+    pub fn new(rule: &str, severity: Severity, command: Option<usize>, error: &Error) -> Self {
+        Finding {
+            rule: rule.to_string(),
+            severity,
+            command,
+            span: None,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]", self.severity, self.rule)?;
+        match (&self.span, self.command) {
+            (Some(span), _) => write!(f, " line {}:{}", span.line, span.column)?,
+            (None, Some(command)) => write!(f, " command {}", command)?,
+            (None, None) => {}
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, finding: Finding) {
+        self.findings.push(finding)
+    }
+
+    pub fn extend(&mut self, findings: impl IntoIterator<Item = Finding>) {
+        self.findings.extend(findings)
+    }
+
+    /// Report is valid if it has no findings of error severity
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == Severity::Error)
+    }
+
+    /// Attach source location to findings, `spans` are indexed by command
+    pub fn with_spans(mut self, spans: &[Span]) -> Self {
+        for finding in &mut self.findings {
+            if let Some(command) = finding.command {
+                finding.span = spans.get(command).copied();
+            }
+        }
+        self
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for finding in &self.findings {
+            writeln!(f, "{}", finding)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_json() {
+        let mut report = ValidationReport::new();
+        report.push(Finding::new(
+            "add-if-not-exist",
+            Severity::Error,
+            Some(1),
+            &Error::AttributeAlreadyExists {
+                attribute: "name".to_string(),
+            },
+        ));
+        report.push(Finding::new(
+            "version",
+            Severity::Warning,
+            None,
+            &Error::MissingVersion(),
+        ));
+        let report = report.with_spans(&[
+            Span {
+                start: 0,
+                end: 23,
+                line: 1,
+                column: 1,
+            },
+            Span {
+                start: 24,
+                end: 47,
+                line: 2,
+                column: 1,
+            },
+        ]);
+        assert!(!report.is_valid());
+
+        let json = report.to_json().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "findings": [
+                    {
+                        "rule": "add-if-not-exist",
+                        "severity": "error",
+                        "command": 1,
+                        "span": { "start": 24, "end": 47, "line": 2, "column": 1 },
+                        "message": "Attribute \"name\" already exists"
+                    },
+                    {
+                        "rule": "version",
+                        "severity": "warning",
+                        "message": "Missing version of OCA AST"
+                    }
+                ]
+            })
+        );
+        assert_eq!(
+            serde_json::from_str::<ValidationReport>(&json).unwrap(),
+            report
+        );
+        assert_eq!(
+            report.to_string(),
+            "error[add-if-not-exist] line 2:1: Attribute \"name\" already exists\n\
+             warning[version]: Missing version of OCA AST\n"
+        );
+    }
+}
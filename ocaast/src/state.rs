@@ -0,0 +1,584 @@
+//! Effective OCA object computed from a stream of commands
+//!
+//! `OCAState` applies commands in order, the same way the bundle would be
+//! built from them. Applying is lenient: removing something which does not
+//! exist or adding something which already exists is not an error here, it
+//! is up to the validator to reject such commands.
+
+use crate::ast::{
+    AttributeType, CaptureBaseContent, Command, CommandType, Content, NestedValue, OCAAst,
+    ObjectKind, OverlayType,
+};
+use indexmap::IndexMap;
+use std::fmt;
+
+/// Identity of overlay within OCA object
+///
+/// Language specific overlays (Label, Information, Meta, ...) are kept per
+/// language, Unit overlays per unit system, the others have neither.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct OverlayKey {
+    pub overlay_type: OverlayType,
+    pub lang: Option<String>,
+    pub system: Option<String>,
+}
+
+impl OverlayKey {
+    pub fn new(overlay_type: &OverlayType, content: &Content) -> Self {
+        OverlayKey {
+            overlay_type: overlay_type.clone(),
+            lang: overlay_lang(content),
+            system: overlay_system(content),
+        }
+    }
+}
+
+impl fmt::Display for OverlayKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.lang.as_ref().or(self.system.as_ref()) {
+            Some(qualifier) => write!(f, "{} ({})", self.overlay_type, qualifier),
+            None => write!(f, "{}", self.overlay_type),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct OCAState {
+    /// SAID of bundle given in FROM command
+    pub from: Option<String>,
+    pub attributes: IndexMap<String, AttributeType>,
+    pub classification: Option<String>,
+    pub flagged_attributes: Vec<String>,
+    pub overlays: IndexMap<OverlayKey, Content>,
+}
+
+impl OCAState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compute state by applying all commands of AST
+    pub fn from_ast(ast: &OCAAst) -> Self {
+        let mut state = Self::new();
+        for command in &ast.commands {
+            state.apply(command);
+        }
+        state
+    }
+
+    /// Apply single command on top of current state
+    ///
+    /// MODIFY is applied like ADD, overwriting existing values.
+    pub fn apply(&mut self, command: &Command) {
+        let content = match &command.content {
+            Some(content) => content,
+            None => return,
+        };
+        match (&command.object_kind, content) {
+            (_, Content::Bundle(content)) => self.from = Some(content.said.clone()),
+            (_, Content::CaptureBase(content)) => self.apply_capture_base(&command.kind, content),
+            (ObjectKind::Overlay(overlay_type), content) => {
+                self.apply_overlay(&command.kind, overlay_type, content)
+            }
+            _ => {}
+        }
+    }
+
+    fn apply_capture_base(&mut self, kind: &CommandType, content: &CaptureBaseContent) {
+        match kind {
+            CommandType::Add | CommandType::Modify => {
+                for (name, attr_type) in &content.attributes {
+                    if let Some(attr_type) = attr_type {
+                        self.attributes.insert(name.clone(), *attr_type);
+                    }
+                }
+                if let Some(classification) = &content.classification {
+                    self.classification = Some(classification.clone());
+                }
+                for name in &content.flagged_attributes {
+                    if !self.flagged_attributes.contains(name) {
+                        self.flagged_attributes.push(name.clone());
+                    }
+                }
+            }
+            CommandType::Remove => {
+                for name in content.attributes.keys() {
+                    self.remove_attribute(name);
+                }
+                if content.classification.is_some() {
+                    self.classification = None;
+                }
+                self.flagged_attributes
+                    .retain(|name| !content.flagged_attributes.contains(name));
+            }
+            CommandType::From => {}
+        }
+    }
+
+    /// Remove attribute together with everything overlays say about it
+    fn remove_attribute(&mut self, name: &str) {
+        self.attributes.shift_remove(name);
+        self.flagged_attributes.retain(|flagged| flagged != name);
+        for content in self.overlays.values_mut() {
+            remove_keys(content, &[name], &[]);
+        }
+        self.overlays.retain(|_, content| !is_empty(content));
+    }
+
+    fn apply_overlay(&mut self, kind: &CommandType, overlay_type: &OverlayType, content: &Content) {
+        let key = OverlayKey::new(overlay_type, content);
+        match kind {
+            CommandType::Add | CommandType::Modify => match self.overlays.get_mut(&key) {
+                Some(existing) => merge(existing, content),
+                None => {
+                    self.overlays.insert(key, content.clone());
+                }
+            },
+            CommandType::Remove => {
+                let attributes = attribute_keys(content);
+                let properties = property_keys(content);
+                // REMOVE without keys drops the whole overlay
+                if attributes.is_empty() && properties.is_empty() {
+                    self.overlays.shift_remove(&key);
+                } else if let Some(existing) = self.overlays.get_mut(&key) {
+                    remove_keys(existing, &attributes, &properties);
+                    if is_empty(existing) {
+                        self.overlays.shift_remove(&key);
+                    }
+                }
+            }
+            CommandType::From => {}
+        }
+    }
+
+    pub fn overlay(&self, overlay_type: &OverlayType, lang: Option<&str>) -> Option<&Content> {
+        self.overlays.get(&OverlayKey {
+            overlay_type: overlay_type.clone(),
+            lang: lang.map(str::to_string),
+            system: None,
+        })
+    }
+
+    /// Unit overlay of given unit system
+    pub fn unit_overlay(&self, system: &str) -> Option<&Content> {
+        self.overlays.get(&OverlayKey {
+            overlay_type: OverlayType::Unit,
+            lang: None,
+            system: Some(system.to_string()),
+        })
+    }
+
+    /// Unit systems of all Unit overlays, in order of appearance
+    pub fn unit_systems(&self) -> Vec<&str> {
+        self.overlays
+            .keys()
+            .filter_map(|key| key.system.as_deref())
+            .collect()
+    }
+
+    /// Languages of all language specific overlays, in order of appearance
+    pub fn languages(&self) -> Vec<&str> {
+        let mut languages = Vec::new();
+        for key in self.overlays.keys() {
+            if let Some(lang) = key.lang.as_deref() {
+                if !languages.contains(&lang) {
+                    languages.push(lang);
+                }
+            }
+        }
+        languages
+    }
+
+    pub fn attribute_type(&self, attribute: &str) -> Option<&AttributeType> {
+        self.attributes.get(attribute)
+    }
+
+    pub fn label(&self, attribute: &str, lang: &str) -> Option<&str> {
+        match self.overlay(&OverlayType::Label, Some(lang)) {
+            Some(Content::Label(content)) => content.attributes.get(attribute).map(String::as_str),
+            _ => None,
+        }
+    }
+
+    pub fn information(&self, attribute: &str, lang: &str) -> Option<&str> {
+        match self.overlay(&OverlayType::Information, Some(lang)) {
+            Some(Content::Information(content)) => {
+                content.attributes.get(attribute).map(String::as_str)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn meta(&self, property: &str, lang: &str) -> Option<&str> {
+        match self.overlay(&OverlayType::Meta, Some(lang)) {
+            Some(Content::Meta(content)) => content.properties.get(property).map(String::as_str),
+            _ => None,
+        }
+    }
+
+    pub fn format(&self, attribute: &str) -> Option<&str> {
+        match self.overlay(&OverlayType::Format, None) {
+            Some(Content::Format(content)) => content.attributes.get(attribute).map(String::as_str),
+            _ => None,
+        }
+    }
+
+    pub fn unit(&self, attribute: &str, system: &str) -> Option<&str> {
+        match self.unit_overlay(system) {
+            Some(Content::Unit(content)) => content.attributes.get(attribute).map(String::as_str),
+            _ => None,
+        }
+    }
+
+    /// Character encoding of attribute, falling back to the default encoding
+    pub fn character_encoding(&self, attribute: &str) -> Option<&str> {
+        match self.overlay(&OverlayType::CharacterEncoding, None) {
+            Some(Content::CharacterEncoding(content)) => content
+                .attributes
+                .get(attribute)
+                .or(content.default_encoding.as_ref())
+                .map(String::as_str),
+            _ => None,
+        }
+    }
+}
+
+/// Language of overlay content, if overlay is language specific
+pub fn overlay_lang(content: &Content) -> Option<String> {
+    match content {
+        Content::Meta(content) => Some(content.lang.clone()),
+        Content::Label(content) => Some(content.lang.clone()),
+        Content::Information(content) => Some(content.lang.clone()),
+        Content::Overlay(content) => match content.properties.get("lang") {
+            Some(NestedValue::Value(lang)) => Some(lang.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Unit system of overlay content, if overlay is Unit overlay
+pub fn overlay_system(content: &Content) -> Option<String> {
+    match content {
+        Content::Unit(content) => Some(content.system.clone()),
+        _ => None,
+    }
+}
+
+/// Attribute keys overlay content refers to
+pub fn attribute_keys(content: &Content) -> Vec<&str> {
+    let keys: Box<dyn Iterator<Item = &String>> = match content {
+        Content::CaptureBase(content) => Box::new(content.attributes.keys()),
+        Content::Label(content) => Box::new(content.attributes.keys()),
+        Content::Information(content) => Box::new(content.attributes.keys()),
+        Content::CharacterEncoding(content) => Box::new(content.attributes.keys()),
+        Content::Format(content) => Box::new(content.attributes.keys()),
+        Content::Unit(content) => Box::new(content.attributes.keys()),
+        Content::Overlay(content) => Box::new(content.attributes.keys()),
+        Content::Meta(_) | Content::Bundle(_) => return Vec::new(),
+    };
+    keys.map(String::as_str).collect()
+}
+
+/// Property keys overlay content refers to, except language
+pub fn property_keys(content: &Content) -> Vec<&str> {
+    match content {
+        Content::Meta(content) => content.properties.keys().map(String::as_str).collect(),
+        Content::CharacterEncoding(content) if content.default_encoding.is_some() => {
+            vec!["default_encoding"]
+        }
+        Content::Overlay(content) => content
+            .properties
+            .keys()
+            .map(String::as_str)
+            .filter(|key| *key != "lang")
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Property keys which clash with fields the overlay has in a bundle
+///
+/// Meta properties and properties of overlays without content model are
+/// fields of the overlay next to its SAID, type and capture base.
+pub fn reserved_property_keys(content: &Content) -> Vec<&str> {
+    let reserved: &[&str] = match content {
+        Content::Meta(_) => &["digest", "type", "capture_base", "language"],
+        Content::Overlay(_) => &["digest", "type", "capture_base", "attributes"],
+        _ => &[],
+    };
+    property_keys(content)
+        .into_iter()
+        .filter(|key| reserved.contains(key))
+        .collect()
+}
+
+fn merge(existing: &mut Content, added: &Content) {
+    match (existing, added) {
+        (Content::Meta(existing), Content::Meta(added)) => {
+            existing.properties.extend(added.properties.clone())
+        }
+        (Content::Label(existing), Content::Label(added)) => {
+            existing.attributes.extend(added.attributes.clone())
+        }
+        (Content::Information(existing), Content::Information(added)) => {
+            existing.attributes.extend(added.attributes.clone())
+        }
+        (Content::CharacterEncoding(existing), Content::CharacterEncoding(added)) => {
+            existing.attributes.extend(added.attributes.clone());
+            if added.default_encoding.is_some() {
+                existing.default_encoding = added.default_encoding.clone();
+            }
+        }
+        (Content::Format(existing), Content::Format(added)) => {
+            existing.attributes.extend(added.attributes.clone())
+        }
+        (Content::Unit(existing), Content::Unit(added)) => {
+            existing.attributes.extend(added.attributes.clone())
+        }
+        (Content::Overlay(existing), Content::Overlay(added)) => {
+            existing.attributes.extend(added.attributes.clone());
+            existing.properties.extend(added.properties.clone());
+        }
+        (existing, added) => *existing = added.clone(),
+    }
+}
+
+fn remove_keys(content: &mut Content, attributes: &[&str], properties: &[&str]) {
+    match content {
+        Content::Meta(content) => content
+            .properties
+            .retain(|key, _| !properties.contains(&key.as_str())),
+        Content::Label(content) => content
+            .attributes
+            .retain(|key, _| !attributes.contains(&key.as_str())),
+        Content::Information(content) => content
+            .attributes
+            .retain(|key, _| !attributes.contains(&key.as_str())),
+        Content::CharacterEncoding(content) => {
+            content
+                .attributes
+                .retain(|key, _| !attributes.contains(&key.as_str()));
+            if properties.contains(&"default_encoding") {
+                content.default_encoding = None;
+            }
+        }
+        Content::Format(content) => content
+            .attributes
+            .retain(|key, _| !attributes.contains(&key.as_str())),
+        Content::Unit(content) => content
+            .attributes
+            .retain(|key, _| !attributes.contains(&key.as_str())),
+        Content::Overlay(content) => {
+            content
+                .attributes
+                .retain(|key, _| !attributes.contains(&key.as_str()));
+            content
+                .properties
+                .retain(|key, _| !properties.contains(&key.as_str()));
+        }
+        Content::CaptureBase(_) | Content::Bundle(_) => {}
+    }
+}
+
+fn is_empty(content: &Content) -> bool {
+    attribute_keys(content).is_empty() && property_keys(content).is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{BundleContent, LabelContent, MetaContent, UnitContent};
+    use indexmap::indexmap;
+
+    fn command(kind: CommandType, object_kind: ObjectKind, content: Content) -> Command {
+        Command {
+            kind,
+            object_kind,
+            content: Some(content),
+        }
+    }
+
+    fn label(lang: &str, attributes: IndexMap<String, String>) -> Content {
+        Content::Label(LabelContent {
+            lang: lang.to_string(),
+            attributes,
+        })
+    }
+
+    #[test]
+    fn test_apply_commands() {
+        let label_kind = ObjectKind::Overlay(OverlayType::Label);
+        let mut ast = OCAAst::new();
+        ast.commands.push(command(
+            CommandType::From,
+            ObjectKind::OCABundle,
+            Content::Bundle(BundleContent {
+                said: "ENmwqnqVxonf_bNZ0hMipOJJY25dxlC8eSY5BbyMCfLJ".to_string(),
+            }),
+        ));
+        ast.commands.push(command(
+            CommandType::Add,
+            ObjectKind::CaptureBase,
+            Content::CaptureBase(CaptureBaseContent {
+                attributes: indexmap! {
+                    "name".to_string() => Some(AttributeType::Text),
+                    "photo".to_string() => Some(AttributeType::Binary),
+                    "height".to_string() => Some(AttributeType::Numeric),
+                },
+                ..Default::default()
+            }),
+        ));
+        ast.commands.push(command(
+            CommandType::Add,
+            ObjectKind::CaptureBase,
+            Content::CaptureBase(CaptureBaseContent {
+                classification: Some("GICS:35102020".to_string()),
+                flagged_attributes: vec!["name".to_string(), "photo".to_string()],
+                ..Default::default()
+            }),
+        ));
+        ast.commands.push(command(
+            CommandType::Add,
+            label_kind.clone(),
+            label(
+                "en",
+                indexmap! {
+                    "name".to_string() => "Name".to_string(),
+                    "photo".to_string() => "Photo".to_string(),
+                },
+            ),
+        ));
+        ast.commands.push(command(
+            CommandType::Add,
+            label_kind.clone(),
+            label("pl", indexmap! { "name".to_string() => "Imię".to_string() }),
+        ));
+        ast.commands.push(command(
+            CommandType::Add,
+            label_kind.clone(),
+            label(
+                "en",
+                indexmap! { "height".to_string() => "Height".to_string() },
+            ),
+        ));
+        ast.commands.push(command(
+            CommandType::Add,
+            ObjectKind::Overlay(OverlayType::Meta),
+            Content::Meta(MetaContent {
+                lang: "en".to_string(),
+                properties: indexmap! { "name".to_string() => "Passport".to_string() },
+            }),
+        ));
+        ast.commands.push(command(
+            CommandType::Add,
+            ObjectKind::Overlay(OverlayType::Unit),
+            Content::Unit(UnitContent {
+                system: "SI".to_string(),
+                attributes: indexmap! { "height".to_string() => "cm".to_string() },
+            }),
+        ));
+        ast.commands.push(command(
+            CommandType::Remove,
+            ObjectKind::CaptureBase,
+            Content::CaptureBase(CaptureBaseContent {
+                attributes: indexmap! { "photo".to_string() => None },
+                ..Default::default()
+            }),
+        ));
+        ast.commands.push(command(
+            CommandType::Remove,
+            label_kind,
+            label("pl", IndexMap::new()),
+        ));
+
+        let state = OCAState::from_ast(&ast);
+
+        assert_eq!(
+            state.from.as_deref(),
+            Some("ENmwqnqVxonf_bNZ0hMipOJJY25dxlC8eSY5BbyMCfLJ")
+        );
+        assert_eq!(
+            state.attributes.keys().collect::<Vec<_>>(),
+            vec!["name", "height"]
+        );
+        assert_eq!(
+            state.attribute_type("height"),
+            Some(&AttributeType::Numeric)
+        );
+        assert_eq!(state.classification.as_deref(), Some("GICS:35102020"));
+        assert_eq!(state.flagged_attributes, vec!["name".to_string()]);
+        assert_eq!(state.label("name", "en"), Some("Name"));
+        assert_eq!(state.label("height", "en"), Some("Height"));
+        assert_eq!(state.label("photo", "en"), None);
+        assert_eq!(state.label("name", "pl"), None);
+        assert_eq!(state.meta("name", "en"), Some("Passport"));
+        assert_eq!(state.unit("height", "SI"), Some("cm"));
+        assert_eq!(state.unit("height", "si"), None);
+        assert_eq!(state.languages(), vec!["en"]);
+    }
+
+    #[test]
+    fn test_apply_unit_systems() {
+        let unit = |system: &str, attribute: &str, unit: &str| {
+            command(
+                CommandType::Add,
+                ObjectKind::Overlay(OverlayType::Unit),
+                Content::Unit(UnitContent {
+                    system: system.to_string(),
+                    attributes: indexmap! { attribute.to_string() => unit.to_string() },
+                }),
+            )
+        };
+        let mut state = OCAState::new();
+        state.apply(&unit("si", "weight", "kg"));
+        state.apply(&unit("imperial", "height", "in"));
+        state.apply(&unit("si", "height", "cm"));
+
+        assert_eq!(state.overlays.len(), 2);
+        let si = OverlayKey::new(
+            &OverlayType::Unit,
+            &Content::Unit(UnitContent {
+                system: "si".to_string(),
+                ..Default::default()
+            }),
+        );
+        assert_eq!(si.to_string(), "Unit (si)");
+        assert_eq!(
+            state.overlays.get(&si),
+            Some(&Content::Unit(UnitContent {
+                system: "si".to_string(),
+                attributes: indexmap! {
+                    "weight".to_string() => "kg".to_string(),
+                    "height".to_string() => "cm".to_string(),
+                },
+            }))
+        );
+        assert_eq!(state.unit_systems(), vec!["si", "imperial"]);
+        assert_eq!(state.unit_overlay("si"), state.overlays.get(&si));
+        assert_eq!(state.unit("weight", "si"), Some("kg"));
+        assert_eq!(state.unit("weight", "imperial"), None);
+        assert_eq!(state.unit("height", "si"), Some("cm"));
+        assert_eq!(state.unit("height", "imperial"), Some("in"));
+    }
+
+    #[test]
+    fn test_apply_is_lenient() {
+        let mut state = OCAState::new();
+        state.apply(&command(
+            CommandType::Remove,
+            ObjectKind::CaptureBase,
+            Content::CaptureBase(CaptureBaseContent {
+                attributes: indexmap! { "missing".to_string() => None },
+                ..Default::default()
+            }),
+        ));
+        state.apply(&command(
+            CommandType::Remove,
+            ObjectKind::Overlay(OverlayType::Label),
+            label("en", indexmap! { "missing".to_string() => "".to_string() }),
+        ));
+
+        assert_eq!(state, OCAState::new());
+    }
+}
@@ -0,0 +1,57 @@
+//! "Did you mean" suggestions for misspelled names
+
+/// Find candidate closest to `name`, if it is close enough to be a likely
+/// typo
+pub fn did_you_mean<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    let max_distance = std::cmp::max(1, name.chars().count() / 3);
+    candidates
+        .map(|candidate| (levenshtein(&name, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Edit distance between two strings, counted in characters
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("imię", "imie"), 1);
+        assert_eq!(levenshtein("name", "name"), 0);
+    }
+
+    #[test]
+    fn test_did_you_mean() {
+        let candidates = ["documentNumber", "fullName", "name"];
+        assert_eq!(
+            did_you_mean("documentNumbr", candidates.into_iter()),
+            Some("documentNumber")
+        );
+        assert_eq!(
+            did_you_mean("FullName", candidates.into_iter()),
+            Some("fullName")
+        );
+        assert_eq!(did_you_mean("nme", candidates.into_iter()), Some("name"));
+        assert_eq!(did_you_mean("height", candidates.into_iter()), None);
+    }
+}
@@ -1,4 +1,15 @@
-use crate::{ast::{OCAAst, Command, CommandType, ObjectKind}, errors::Error};
+use crate::{
+    ast::{AttributeType, CaptureBaseContent, Command, CommandType, Content, OCAAst, ObjectKind},
+    coverage::LanguageCoverageRule,
+    errors::Error,
+    format::check_format,
+    registries::{is_character_encoding, unit_system},
+    report::{Finding, Severity, ValidationReport},
+    state::{attribute_keys, property_keys, reserved_property_keys, OCAState, OverlayKey},
+    suggestions::did_you_mean,
+    visit::Visit,
+};
+use indexmap::IndexMap;
 
 /// Validates given commands against existing valid OCA AST
 ///
@@ -7,168 +18,967 @@ use crate::{ast::{OCAAst, Command, CommandType, ObjectKind}, errors::Error};
 /// * `command` - Command to validate against AST
 ///
 /// # Returns
-/// * `Result<bool, Error>` - Result of validation
+/// * `ValidationReport` - Findings of validation, command index of findings
+///   is the index the command would get when appended to AST
 pub trait Validator {
-    fn validate(&self, ast: &OCAAst, command: Command) -> Result<bool, Error>;
+    fn validate(&self, ast: &OCAAst, command: Command) -> ValidationReport;
+
+    /// Validates whole OCA AST, command by command, each against the state
+    /// built from the commands preceding it
+    fn validate_ast(&self, ast: &OCAAst) -> ValidationReport {
+        self.validate_ast_from(ast, OCAState::new())
+    }
+
+    /// Validates whole OCA AST on top of given state, e.g. the state of the
+    /// bundle the AST extends with FROM
+    fn validate_ast_from(&self, ast: &OCAAst, state: OCAState) -> ValidationReport;
+}
+
+/// Single validation rule
+///
+/// Rule is checked against every command, with the state built from the
+/// commands preceding it, and once against the state of the whole AST.
+pub trait Rule {
+    /// Id by which the rule is enabled, disabled and configured
+    fn id(&self) -> &str;
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    /// Configure rule with its specific options
+    fn configure(&mut self, _options: &serde_json::Value) -> Result<(), String> {
+        Err("rule has no options".to_string())
+    }
+
+    fn check_command(&self, _state: &OCAState, _command: &Command) -> Vec<Error> {
+        Vec::new()
+    }
+
+    fn check_state(&self, _state: &OCAState) -> Vec<Error> {
+        Vec::new()
+    }
+}
+
+struct RuleEntry {
+    rule: Box<dyn Rule>,
+    enabled: bool,
+    severity: Severity,
 }
 
-pub struct OCAValidator {}
+/// Registry of validation rules
+///
+/// `OCAValidator::new()` registers the built-in rules, more can be added with
+/// `register`.
+pub struct OCAValidator {
+    rules: IndexMap<String, RuleEntry>,
+}
+
+impl Default for OCAValidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OCAValidator {
+    /// Validator with all built-in rules enabled
+    pub fn new() -> Self {
+        let mut validator = Self::empty();
+        for (id, check) in RULES_1_0_0 {
+            validator.register(CommandRule { id, check });
+        }
+        validator.register(LanguageCoverageRule::default());
+        validator
+    }
+
+    /// Validator without any rules
+    pub fn empty() -> Self {
+        OCAValidator {
+            rules: IndexMap::new(),
+        }
+    }
+
+    /// Register enabled rule, replacing rule with the same id if any
+    pub fn register(&mut self, rule: impl Rule + 'static) {
+        let entry = RuleEntry {
+            severity: rule.severity(),
+            enabled: true,
+            rule: Box::new(rule),
+        };
+        self.rules.insert(entry.rule.id().to_string(), entry);
+    }
+
+    pub fn rule_ids(&self) -> impl Iterator<Item = &str> {
+        self.rules.keys().map(String::as_str)
+    }
+
+    pub fn is_enabled(&self, id: &str) -> bool {
+        self.rules.get(id).is_some_and(|entry| entry.enabled)
+    }
+
+    pub fn enable(&mut self, id: &str) -> Result<(), Error> {
+        self.entry_mut(id)?.enabled = true;
+        Ok(())
+    }
+
+    pub fn disable(&mut self, id: &str) -> Result<(), Error> {
+        self.entry_mut(id)?.enabled = false;
+        Ok(())
+    }
+
+    /// Override severity of findings reported by rule
+    pub fn set_severity(&mut self, id: &str, severity: Severity) -> Result<(), Error> {
+        self.entry_mut(id)?.severity = severity;
+        Ok(())
+    }
+
+    pub fn configure(&mut self, id: &str, options: &serde_json::Value) -> Result<(), Error> {
+        self.entry_mut(id)?
+            .rule
+            .configure(options)
+            .map_err(|reason| Error::InvalidRuleConfiguration {
+                rule: id.to_string(),
+                reason,
+            })
+    }
+
+    fn entry_mut(&mut self, id: &str) -> Result<&mut RuleEntry, Error> {
+        self.rules
+            .get_mut(id)
+            .ok_or_else(|| Error::UnknownRule(id.to_string()))
+    }
+
+    fn enabled_rules(&self) -> impl Iterator<Item = (&str, &RuleEntry)> {
+        self.rules
+            .iter()
+            .filter(|(_, entry)| entry.enabled)
+            .map(|(id, entry)| (id.as_str(), entry))
+    }
+
+    fn check_command(&self, state: &OCAState, index: usize, command: &Command) -> Vec<Finding> {
+        self.enabled_rules()
+            .flat_map(|(id, entry)| {
+                entry
+                    .rule
+                    .check_command(state, command)
+                    .into_iter()
+                    .map(move |error| Finding::new(id, entry.severity, Some(index), &error))
+            })
+            .collect()
+    }
+
+    fn check_state(&self, state: &OCAState) -> Vec<Finding> {
+        self.enabled_rules()
+            .flat_map(|(id, entry)| {
+                entry
+                    .rule
+                    .check_state(state)
+                    .into_iter()
+                    .map(move |error| Finding::new(id, entry.severity, None, &error))
+            })
+            .collect()
+    }
+}
 
 impl Validator for OCAValidator {
-    fn validate(&self, ast: &OCAAst, command: Command) -> Result<bool, Error> {
-        let mut errors = Vec::new();
-        let mut valid = true;
-        match ast.version.as_str() {
-            "1.0.0" => {
-                let version_validator = validate_1_0_0(ast, command);
-                if version_validator.is_err() {
-                    valid = false;
-                    errors.push(version_validator.err().unwrap());
-                }
-            }
-            "" => {
-                valid = false;
-                errors.push(Error::MissingVersion());
-            }
-            _ => {
-                valid = false;
-                errors.push(Error::InvalidVersion(ast.version.to_string()));
-            }
+    fn validate(&self, ast: &OCAAst, command: Command) -> ValidationReport {
+        let mut report = ValidationReport::new();
+        if let Err(finding) = check_version(ast) {
+            report.push(finding);
+            return report;
         }
-        if valid {
-            Ok(true)
-        } else {
-            Err(Error::Validation(errors))
+        let state = OCAState::from_ast(ast);
+        report.extend(self.check_command(&state, ast.commands.len(), &command));
+        report
+    }
+
+    fn validate_ast_from(&self, ast: &OCAAst, mut state: OCAState) -> ValidationReport {
+        let mut report = ValidationReport::new();
+        if let Err(finding) = check_version(ast) {
+            report.push(finding);
+            return report;
+        }
+        for (index, command) in ast.commands.iter().enumerate() {
+            report.extend(self.check_command(&state, index, command));
+            state.apply(command);
         }
+        report.extend(self.check_state(&state));
+        report
     }
 }
 
-fn validate_1_0_0(ast: &OCAAst, command: Command) -> Result<(bool), Error> {
-    // Rules
+fn check_version(ast: &OCAAst) -> Result<(), Finding> {
+    let error = match ast.version.as_str() {
+        "1.0.0" => return Ok(()),
+        "" => Error::MissingVersion(),
+        version => Error::InvalidVersion(version.to_string()),
+    };
+    Err(Finding::new("version", Severity::Error, None, &error))
+}
+
+type RuleCheck = fn(&OCAState, &Command) -> Vec<Error>;
+
+/// Rules of OCA 1.0.0 with their ids
+const RULES_1_0_0: [(&str, RuleCheck); 9] = [
     // Cannot remove if does not exist on stack
+    ("remove-if-exist", rule_remove_if_exist),
     // Cannot modify if does not exist on stack
+    ("modify-if-exist", rule_modify_if_exist),
     // Cannot add if already exists on stack
+    ("add-if-not-exist", rule_add_if_not_exist),
     // Attributes must have valid type
-    let mut valid = true;
-    let mut errors = Vec::new();
+    ("valid-attribute-type", rule_valid_attribute_type),
+    // Overlays can only refer to attributes of capture base
+    ("overlay-attributes-exist", rule_overlay_attributes_exist),
+    // Format must match type of attribute
+    ("valid-format", rule_valid_format),
+    // Character encodings must be known
+    ("valid-character-encoding", rule_valid_character_encoding),
+    // Units must be known and only given for numeric attributes
+    ("valid-unit", rule_valid_unit),
+    // Overlay properties can't clash with fields of overlay in bundle
+    ("no-reserved-properties", rule_no_reserved_properties),
+];
 
-    for command in &ast.commands {
+/// Built-in rule checking commands only
+struct CommandRule {
+    id: &'static str,
+    check: RuleCheck,
+}
 
+impl Rule for CommandRule {
+    fn id(&self) -> &str {
+        self.id
     }
-    if valid {
-        Ok(true)
-    } else {
-        Err(Error::Validation(errors))
+
+    fn check_command(&self, state: &OCAState, command: &Command) -> Vec<Error> {
+        (self.check)(state, command)
     }
 }
 
 /// Check rule for remove command
-/// Rule would be valid if attributes which commands tries to remove exist in the stack
+/// Rule would be valid if attributes, classification and overlays which command tries to remove exist in the stack
 ///
 /// # Arguments
-/// * `ast` - valid OCA AST
-/// * `command` - Command to validate against AST
+/// * `state` - state built from valid OCA AST
+/// * `command` - Command to validate against state
 ///
 /// # Returns
-/// * `Result<bool, Error>` - Result of validation
-fn rule_remove_if_exist(ast: &OCAAst, command: Command) -> Result<bool, Error> {
-    let mut valid = true;
-    let mut errors = Vec::new();
-    // Create a list of all attributes ADDed and REMOVEd via commands and check if what left covers needs of new command
-    let mut attributes: Vec<String> = Vec::new();
-    for command in &ast.commands {
-        match command.kind {
-            CommandType::Remove => {
-                if command.object_kind == ObjectKind::CaptureBase {
-                    let attrs = command.content.as_ref().unwrap().attributes.as_ref().unwrap();
-                    attributes.retain(|key| !attrs.contains_key(key));
-                }
+/// * `Vec<Error>` - Violations of the rule
+fn rule_remove_if_exist(state: &OCAState, command: &Command) -> Vec<Error> {
+    match command.kind {
+        CommandType::Remove => check_existence(state, command),
+        _ => Vec::new(),
+    }
+}
+
+/// Check rule for modify command
+/// Rule would be valid if attributes and overlays which command tries to modify exist in the stack
+fn rule_modify_if_exist(state: &OCAState, command: &Command) -> Vec<Error> {
+    match command.kind {
+        CommandType::Modify => check_existence(state, command),
+        _ => Vec::new(),
+    }
+}
+
+/// Check rule for add command
+/// Rule would be valid if attributes which command tries to add do not exist in the stack yet
+fn rule_add_if_not_exist(state: &OCAState, command: &Command) -> Vec<Error> {
+    check_attributes(command, |kind, attribute, _| {
+        (*kind == CommandType::Add && state.attributes.contains_key(attribute)).then(|| {
+            Error::AttributeAlreadyExists {
+                attribute: attribute.to_string(),
             }
-            CommandType::Add => {
-                if command.object_kind == ObjectKind::CaptureBase {
-                    let attrs = command.content.as_ref().unwrap().attributes.as_ref().unwrap();
-                    attributes.extend(attrs.keys().cloned());
-                }
+        })
+    })
+}
+
+/// Check rule for attribute types
+/// Rule would be valid if every attribute added or modified has a type
+fn rule_valid_attribute_type(_state: &OCAState, command: &Command) -> Vec<Error> {
+    check_attributes(command, |kind, attribute, attr_type| {
+        (matches!(kind, CommandType::Add | CommandType::Modify) && attr_type.is_none()).then(|| {
+            Error::InvalidAttributeType {
+                attribute: attribute.to_string(),
             }
-            _ => {}
+        })
+    })
+}
+
+/// Check rule for overlays
+/// Rule would be valid if every attribute which overlay added or modified by command refers to exists in capture base
+fn rule_overlay_attributes_exist(state: &OCAState, command: &Command) -> Vec<Error> {
+    match (&command.kind, &command.object_kind, &command.content) {
+        (
+            CommandType::Add | CommandType::Modify,
+            ObjectKind::Overlay(overlay_type),
+            Some(content),
+        ) => attribute_keys(content)
+            .into_iter()
+            .filter(|attribute| !state.attributes.contains_key(*attribute))
+            .map(|attribute| Error::UnknownAttribute {
+                overlay: OverlayKey::new(overlay_type, content).to_string(),
+                attribute: attribute.to_string(),
+                suggestion: did_you_mean(attribute, state.attributes.keys().map(String::as_str))
+                    .map(str::to_string),
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Check rule for format overlay
+/// Rule would be valid if every format added or modified by command is meaningful for type of its attribute
+fn rule_valid_format(state: &OCAState, command: &Command) -> Vec<Error> {
+    match (&command.kind, &command.content) {
+        (CommandType::Add | CommandType::Modify, Some(Content::Format(content))) => content
+            .attributes
+            .iter()
+            .filter_map(|(attribute, format)| {
+                let attr_type = state.attribute_type(attribute)?;
+                let reason = check_format(attr_type, format).err()?;
+                Some(Error::InvalidFormat {
+                    attribute: attribute.clone(),
+                    format: format.clone(),
+                    reason,
+                })
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Check rule for character encoding overlay
+/// Rule would be valid if every encoding added or modified by command is in the registry of character encodings
+fn rule_valid_character_encoding(_state: &OCAState, command: &Command) -> Vec<Error> {
+    match (&command.kind, &command.content) {
+        (CommandType::Add | CommandType::Modify, Some(Content::CharacterEncoding(content))) => {
+            let default_encoding = content
+                .default_encoding
+                .as_ref()
+                .map(|encoding| ("default_encoding", encoding));
+            content
+                .attributes
+                .iter()
+                .map(|(attribute, encoding)| (attribute.as_str(), encoding))
+                .chain(default_encoding)
+                .filter(|(_, encoding)| !is_character_encoding(encoding))
+                .map(|(key, encoding)| Error::UnknownCharacterEncoding {
+                    key: key.to_string(),
+                    encoding: encoding.clone(),
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Check rule for unit overlay
+/// Rule would be valid if unit system and units added or modified by command are in the registry of unit systems
+/// and all attributes command gives units to are numeric
+fn rule_valid_unit(state: &OCAState, command: &Command) -> Vec<Error> {
+    let content = match (&command.kind, &command.content) {
+        (CommandType::Add | CommandType::Modify, Some(Content::Unit(content))) => content,
+        _ => return Vec::new(),
+    };
+    let system = unit_system(&content.system);
+    let mut errors = Vec::new();
+    if system.is_none() {
+        errors.push(Error::UnknownUnitSystem(content.system.clone()));
+    }
+    for (attribute, unit) in &content.attributes {
+        match state.attribute_type(attribute) {
+            Some(AttributeType::Numeric | AttributeType::ArrayNumeric) | None => {}
+            Some(attr_type) => errors.push(Error::UnitOnNonNumeric {
+                attribute: attribute.clone(),
+                attr_type: attr_type.to_string(),
+            }),
+        }
+        if let Some(system) = system.filter(|system| !system.has_unit(unit)) {
+            errors.push(Error::UnknownUnit {
+                attribute: attribute.clone(),
+                unit: unit.clone(),
+                system: system.name.to_string(),
+            });
+        }
+    }
+    errors
+}
+
+/// Check rule for overlay properties
+/// Rule would be valid if no property added or modified by command is reserved for a field of overlay in bundle
+fn rule_no_reserved_properties(_state: &OCAState, command: &Command) -> Vec<Error> {
+    match (&command.kind, &command.object_kind, &command.content) {
+        (
+            CommandType::Add | CommandType::Modify,
+            ObjectKind::Overlay(overlay_type),
+            Some(content),
+        ) => reserved_property_keys(content)
+            .into_iter()
+            .map(|key| Error::ReservedProperty {
+                overlay: OverlayKey::new(overlay_type, content).to_string(),
+                key: key.to_string(),
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Report everything command refers to which does not exist in the state
+fn check_existence(state: &OCAState, command: &Command) -> Vec<Error> {
+    match (&command.object_kind, &command.content) {
+        (_, Some(Content::CaptureBase(content))) => {
+            check_capture_base_existence(state, &command.kind, content)
+        }
+        (ObjectKind::Overlay(overlay_type), Some(content)) => {
+            let key = OverlayKey::new(overlay_type, content);
+            let existing = match state.overlays.get(&key) {
+                Some(existing) => existing,
+                None => {
+                    return vec![Error::OverlayNotFound {
+                        overlay: key.to_string(),
+                    }]
+                }
+            };
+            let existing_attributes = attribute_keys(existing);
+            let existing_properties = property_keys(existing);
+            let missing_attributes = attribute_keys(content)
+                .into_iter()
+                .filter(|key| !existing_attributes.contains(key));
+            let missing_properties = property_keys(content)
+                .into_iter()
+                .filter(|key| !existing_properties.contains(key));
+            missing_attributes
+                .chain(missing_properties)
+                .map(|missing| Error::OverlayKeyNotFound {
+                    overlay: key.to_string(),
+                    key: missing.to_string(),
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn check_capture_base_existence(
+    state: &OCAState,
+    kind: &CommandType,
+    content: &CaptureBaseContent,
+) -> Vec<Error> {
+    let mut visitor = CaptureBaseExistence {
+        state,
+        errors: Vec::new(),
+    };
+    visitor.visit_capture_base(kind, content);
+    visitor.errors
+}
+
+/// Collects attributes and classification of capture base which are missing
+/// from the state
+struct CaptureBaseExistence<'a> {
+    state: &'a OCAState,
+    errors: Vec<Error>,
+}
+
+impl CaptureBaseExistence<'_> {
+    fn check_attribute(&mut self, attribute: &str) {
+        if !self.state.attributes.contains_key(attribute) {
+            self.errors.push(Error::AttributeNotFound {
+                attribute: attribute.to_string(),
+            });
+        }
+    }
+}
+
+impl Visit for CaptureBaseExistence<'_> {
+    fn visit_attribute(
+        &mut self,
+        _kind: &CommandType,
+        name: &str,
+        _attr_type: Option<&AttributeType>,
+    ) {
+        self.check_attribute(name)
+    }
+
+    fn visit_classification(&mut self, _kind: &CommandType, _classification: &str) {
+        if self.state.classification.is_none() {
+            self.errors.push(Error::ClassificationNotFound);
         }
     }
-    let keys_to_remove = command.content.as_ref().unwrap().attributes.as_ref().unwrap();
-    valid = keys_to_remove.keys().all(|key| attributes.contains(key));
 
-    if valid {
-        Ok(true)
-    } else {
-        Err(Error::Validation(errors))
+    fn visit_flagged_attribute(&mut self, _kind: &CommandType, name: &str) {
+        self.check_attribute(name)
     }
 }
 
+/// Check every attribute of capture base command, collecting the errors
+fn check_attributes<F>(command: &Command, check: F) -> Vec<Error>
+where
+    F: FnMut(&CommandType, &str, Option<&AttributeType>) -> Option<Error>,
+{
+    let mut visitor = AttributeCheck {
+        check,
+        errors: Vec::new(),
+    };
+    if let Some(Content::CaptureBase(content)) = &command.content {
+        visitor.visit_capture_base(&command.kind, content);
+    }
+    visitor.errors
+}
+
+struct AttributeCheck<F> {
+    check: F,
+    errors: Vec<Error>,
+}
+
+impl<F> Visit for AttributeCheck<F>
+where
+    F: FnMut(&CommandType, &str, Option<&AttributeType>) -> Option<Error>,
+{
+    fn visit_attribute(
+        &mut self,
+        kind: &CommandType,
+        name: &str,
+        attr_type: Option<&AttributeType>,
+    ) {
+        self.errors.extend((self.check)(kind, name, attr_type));
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use indexmap::indexmap;
 
     use super::*;
-    use crate::ast::{OCAAst, Command, CommandType, ObjectKind, Content, NestedValue};
+    use crate::ast::{
+        CharacterEncodingContent, FormatContent, LabelContent, MetaContent, OverlayType,
+        UnitContent,
+    };
 
     #[test]
     fn test_rule_remove_if_exist() {
         let command = Command {
             kind: CommandType::Add,
             object_kind: ObjectKind::CaptureBase,
-            content: Some(Content {
-                attributes: Some(indexmap! {
-                    "name".to_string() => NestedValue::Value("Text".to_string()),
-                    "documentType".to_string() => NestedValue::Value("Text".to_string()),
-                    "photo".to_string() => NestedValue::Value("Binary".to_string()),
-                }),
-                properties: None,
-            }),
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes: indexmap! {
+                    "name".to_string() => Some(AttributeType::Text),
+                    "documentType".to_string() => Some(AttributeType::Text),
+                    "photo".to_string() => Some(AttributeType::Binary),
+                },
+                ..Default::default()
+            })),
         };
 
         let command2 = Command {
             kind: CommandType::Add,
             object_kind: ObjectKind::CaptureBase,
-            content: Some(Content {
-                attributes: Some(indexmap! {
-                    "issuer".to_string() => NestedValue::Value("Text".to_string()),
-                    "last_name".to_string() => NestedValue::Value("Binary".to_string()),
-                }),
-                properties: None,
-            }),
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes: indexmap! {
+                    "issuer".to_string() => Some(AttributeType::Text),
+                    "last_name".to_string() => Some(AttributeType::Binary),
+                },
+                ..Default::default()
+            })),
         };
 
         let remove_command = Command {
             kind: CommandType::Remove,
             object_kind: ObjectKind::CaptureBase,
-            content: Some(Content {
-                attributes: Some(indexmap! {
-                    "name".to_string() => NestedValue::Value("".to_string()),
-                    "issuer".to_string() => NestedValue::Value("".to_string()),
-                }),
-                properties: None,
-            }),
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes: indexmap! {
+                    "name".to_string() => None,
+                    "issuer".to_string() => None,
+                },
+                ..Default::default()
+            })),
         };
 
         let remove_command2 = Command {
             kind: CommandType::Remove,
             object_kind: ObjectKind::CaptureBase,
-            content: Some(Content {
-                attributes: Some(indexmap! {
-                    "name".to_string() => NestedValue::Value("".to_string()),
-                    "photo".to_string() => NestedValue::Value("".to_string()),
-                }),
-                properties: None,
-            }),
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes: indexmap! {
+                    "name".to_string() => None,
+                    "photo".to_string() => None,
+                },
+                ..Default::default()
+            })),
         };
 
         let mut ocaast = OCAAst::new();
         ocaast.commands.push(command);
         ocaast.commands.push(command2);
-        let mut result = rule_remove_if_exist(&ocaast, remove_command.clone());
-        assert!(result.is_ok());
+        let mut state = OCAState::from_ast(&ocaast);
+        let mut result = rule_remove_if_exist(&state, &remove_command);
+        assert!(result.is_empty());
         ocaast.commands.push(remove_command2);
-        result = rule_remove_if_exist(&ocaast, remove_command);
-        assert!(result.is_err());
+        state = OCAState::from_ast(&ocaast);
+        result = rule_remove_if_exist(&state, &remove_command);
+        assert_eq!(
+            result,
+            vec![Error::AttributeNotFound {
+                attribute: "name".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_ast() {
+        let add_attributes = Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::CaptureBase,
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes: indexmap! {
+                    "name".to_string() => Some(AttributeType::Text),
+                    "photo".to_string() => None,
+                },
+                ..Default::default()
+            })),
+        };
+        let add_name_again = Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::CaptureBase,
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes: indexmap! { "name".to_string() => Some(AttributeType::Text) },
+                ..Default::default()
+            })),
+        };
+        let remove_label = Command {
+            kind: CommandType::Remove,
+            object_kind: ObjectKind::Overlay(OverlayType::Label),
+            content: Some(Content::Label(LabelContent {
+                lang: "en".to_string(),
+                attributes: IndexMap::new(),
+            })),
+        };
+        let remove_classification = Command {
+            kind: CommandType::Remove,
+            object_kind: ObjectKind::CaptureBase,
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                classification: Some("".to_string()),
+                ..Default::default()
+            })),
+        };
+
+        let validator = OCAValidator::new();
+        let mut ocaast = OCAAst::new();
+        ocaast.commands.push(add_attributes);
+        ocaast.commands.push(add_name_again);
+        ocaast.commands.push(remove_label);
+        ocaast.commands.push(remove_classification);
+
+        let report = validator.validate_ast(&ocaast);
+        assert!(!report.is_valid());
+        assert_eq!(
+            report.findings,
+            vec![
+                Finding::new(
+                    "valid-attribute-type",
+                    Severity::Error,
+                    Some(0),
+                    &Error::InvalidAttributeType {
+                        attribute: "photo".to_string(),
+                    },
+                ),
+                Finding::new(
+                    "add-if-not-exist",
+                    Severity::Error,
+                    Some(1),
+                    &Error::AttributeAlreadyExists {
+                        attribute: "name".to_string(),
+                    },
+                ),
+                Finding::new(
+                    "remove-if-exist",
+                    Severity::Error,
+                    Some(2),
+                    &Error::OverlayNotFound {
+                        overlay: "Label (en)".to_string(),
+                    },
+                ),
+                Finding::new(
+                    "remove-if-exist",
+                    Severity::Error,
+                    Some(3),
+                    &Error::ClassificationNotFound,
+                ),
+            ]
+        );
+
+        ocaast.commands.truncate(1);
+        ocaast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::Label),
+            content: Some(Content::Label(LabelContent {
+                lang: "en".to_string(),
+                attributes: indexmap! {
+                    "name".to_string() => "Name".to_string(),
+                    "nme".to_string() => "Name".to_string(),
+                    "height".to_string() => "Height".to_string(),
+                },
+            })),
+        });
+        let messages: Vec<String> = validator
+            .validate_ast(&ocaast)
+            .findings
+            .into_iter()
+            .skip(1)
+            .map(|finding| finding.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "error[overlay-attributes-exist] command 1: Label (en) overlay refers to attribute \"nme\" which is not in capture base, did you mean \"name\"?",
+                "error[overlay-attributes-exist] command 1: Label (en) overlay refers to attribute \"height\" which is not in capture base",
+            ]
+        );
+
+        ocaast.version = "".to_string();
+        assert_eq!(
+            validator.validate_ast(&ocaast).findings,
+            vec![Finding::new(
+                "version",
+                Severity::Error,
+                None,
+                &Error::MissingVersion()
+            )]
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_rule_valid_format() {
+        let mut ocaast = OCAAst::new();
+        ocaast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::CaptureBase,
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes: indexmap! {
+                    "documentNumber".to_string() => Some(AttributeType::Text),
+                    "photo".to_string() => Some(AttributeType::Binary),
+                },
+                ..Default::default()
+            })),
+        });
+        let state = OCAState::from_ast(&ocaast);
+        let command = Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::Format),
+            content: Some(Content::Format(FormatContent {
+                attributes: indexmap! {
+                    "documentNumber".to_string() => "[A-Z0-9]{9}".to_string(),
+                    "photo".to_string() => "[A-Z0-9]{9}".to_string(),
+                },
+            })),
+        };
+
+        assert_eq!(
+            rule_valid_format(&state, &command),
+            vec![Error::InvalidFormat {
+                attribute: "photo".to_string(),
+                format: "[A-Z0-9]{9}".to_string(),
+                reason: "expected MIME type like image/jpeg".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_rule_valid_encoding_and_unit() {
+        let mut ocaast = OCAAst::new();
+        ocaast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::CaptureBase,
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes: indexmap! {
+                    "height".to_string() => Some(AttributeType::Numeric),
+                    "name".to_string() => Some(AttributeType::Text),
+                },
+                ..Default::default()
+            })),
+        });
+        let state = OCAState::from_ast(&ocaast);
+
+        let encoding = Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::CharacterEncoding),
+            content: Some(Content::CharacterEncoding(CharacterEncodingContent {
+                attributes: indexmap! { "name".to_string() => "utf-8".to_string() },
+                default_encoding: Some("utf-7.5".to_string()),
+            })),
+        };
+        assert_eq!(
+            rule_valid_character_encoding(&state, &encoding),
+            vec![Error::UnknownCharacterEncoding {
+                key: "default_encoding".to_string(),
+                encoding: "utf-7.5".to_string(),
+            }]
+        );
+
+        let unit = Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::Unit),
+            content: Some(Content::Unit(UnitContent {
+                system: "si".to_string(),
+                attributes: indexmap! {
+                    "height".to_string() => "ft".to_string(),
+                    "name".to_string() => "cm".to_string(),
+                },
+            })),
+        };
+        assert_eq!(
+            rule_valid_unit(&state, &unit),
+            vec![
+                Error::UnknownUnit {
+                    attribute: "height".to_string(),
+                    unit: "ft".to_string(),
+                    system: "si".to_string(),
+                },
+                Error::UnitOnNonNumeric {
+                    attribute: "name".to_string(),
+                    attr_type: "Text".to_string(),
+                },
+            ]
+        );
+    }
+
+    /// Attribute names must be camelCase
+    struct CamelCaseAttributes;
+
+    impl Rule for CamelCaseAttributes {
+        fn id(&self) -> &str {
+            "camel-case-attributes"
+        }
+
+        fn severity(&self) -> Severity {
+            Severity::Warning
+        }
+
+        fn check_command(&self, _state: &OCAState, command: &Command) -> Vec<Error> {
+            match (&command.kind, &command.content) {
+                (CommandType::Add, Some(Content::CaptureBase(content))) => content
+                    .attributes
+                    .keys()
+                    .filter(|name| name.contains(['_', '-', ' ']))
+                    .map(|name| Error::Rule(format!("Attribute {:?} is not camelCase", name)))
+                    .collect(),
+                _ => Vec::new(),
+            }
+        }
+    }
+
+    /// Meta overlay must have given property in given language
+    struct MandatoryMeta {
+        property: String,
+        lang: String,
+    }
+
+    impl Rule for MandatoryMeta {
+        fn id(&self) -> &str {
+            "mandatory-meta"
+        }
+
+        fn configure(&mut self, options: &serde_json::Value) -> Result<(), String> {
+            match (options["property"].as_str(), options["lang"].as_str()) {
+                (Some(property), Some(lang)) => {
+                    self.property = property.to_string();
+                    self.lang = lang.to_string();
+                    Ok(())
+                }
+                _ => Err("expected \"property\" and \"lang\" options".to_string()),
+            }
+        }
+
+        fn check_state(&self, state: &OCAState) -> Vec<Error> {
+            match state.meta(&self.property, &self.lang) {
+                Some(_) => Vec::new(),
+                None => vec![Error::Rule(format!(
+                    "Missing {:?} meta in {}",
+                    self.property, self.lang
+                ))],
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_no_reserved_properties() {
+        let meta = Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::Meta),
+            content: Some(Content::Meta(MetaContent {
+                lang: "en".to_string(),
+                properties: indexmap! {
+                    "type".to_string() => "x".to_string(),
+                    "name".to_string() => "Passport".to_string(),
+                },
+            })),
+        };
+        assert_eq!(
+            rule_no_reserved_properties(&OCAState::new(), &meta),
+            vec![Error::ReservedProperty {
+                overlay: "Meta (en)".to_string(),
+                key: "type".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_rule_registry() {
+        let mut ocaast = OCAAst::new();
+        ocaast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::CaptureBase,
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes: indexmap! {
+                    "first_name".to_string() => Some(AttributeType::Text),
+                },
+                ..Default::default()
+            })),
+        });
+        ocaast.commands.push(ocaast.commands[0].clone());
+
+        let mut validator = OCAValidator::new();
+        assert!(validator.rule_ids().any(|id| id == "add-if-not-exist"));
+        validator.register(CamelCaseAttributes);
+        validator.register(MandatoryMeta {
+            property: "name".to_string(),
+            lang: "en".to_string(),
+        });
+        validator.disable("add-if-not-exist").unwrap();
+        validator
+            .configure(
+                "mandatory-meta",
+                &serde_json::json!({ "property": "description", "lang": "en" }),
+            )
+            .unwrap();
+
+        let report = validator.validate_ast(&ocaast);
+        assert!(!report.is_valid());
+        assert_eq!(
+            report.to_string(),
+            "warning[camel-case-attributes] command 0: Attribute \"first_name\" is not camelCase\n\
+             warning[camel-case-attributes] command 1: Attribute \"first_name\" is not camelCase\n\
+             error[mandatory-meta]: Missing \"description\" meta in en\n"
+        );
+
+        validator
+            .set_severity("mandatory-meta", Severity::Info)
+            .unwrap();
+        validator.disable("camel-case-attributes").unwrap();
+        validator.enable("add-if-not-exist").unwrap();
+        let report = validator.validate_ast(&ocaast);
+        assert_eq!(
+            report.to_string(),
+            "error[add-if-not-exist] command 1: Attribute \"first_name\" already exists\n\
+             info[mandatory-meta]: Missing \"description\" meta in en\n"
+        );
+
+        assert_eq!(
+            validator.disable("unknown"),
+            Err(Error::UnknownRule("unknown".to_string()))
+        );
+        assert!(matches!(
+            validator.configure("add-if-not-exist", &serde_json::json!({})),
+            Err(Error::InvalidRuleConfiguration { .. })
+        ));
+    }
+}
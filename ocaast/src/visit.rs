@@ -0,0 +1,1169 @@
+//! Traversal of OCA AST
+//!
+//! `Visit` and `VisitMut` walk the AST by reference, `Fold` consumes it and
+//! rebuilds it. Every hook has a default implementation which calls the
+//! matching `walk_*` function, so implementors override only the hooks they
+//! are interested in and call the walker to keep descending.
+
+use crate::ast::{
+    AttributeType, BundleContent, CaptureBaseContent, CharacterEncodingContent, Command,
+    CommandType, Content, FormatContent, InformationContent, LabelContent, MetaContent,
+    NestedValue, OCAAst, ObjectKind, OverlayContent, OverlayType, UnitContent,
+};
+use indexmap::IndexMap;
+
+/// Read-only traversal of OCA AST
+pub trait Visit {
+    fn visit_ast(&mut self, ast: &OCAAst) {
+        walk_ast(self, ast)
+    }
+
+    fn visit_command(&mut self, index: usize, command: &Command) {
+        walk_command(self, index, command)
+    }
+
+    fn visit_capture_base(&mut self, kind: &CommandType, content: &CaptureBaseContent) {
+        walk_capture_base(self, kind, content)
+    }
+
+    fn visit_attribute(
+        &mut self,
+        _kind: &CommandType,
+        _name: &str,
+        _attr_type: Option<&AttributeType>,
+    ) {
+    }
+
+    fn visit_classification(&mut self, _kind: &CommandType, _classification: &str) {}
+
+    fn visit_flagged_attribute(&mut self, _kind: &CommandType, _name: &str) {}
+
+    fn visit_bundle(&mut self, _kind: &CommandType, _content: &BundleContent) {}
+
+    fn visit_overlay(&mut self, kind: &CommandType, overlay_type: &OverlayType, content: &Content) {
+        walk_overlay(self, kind, overlay_type, content)
+    }
+
+    fn visit_meta(&mut self, kind: &CommandType, content: &MetaContent) {
+        walk_meta(self, kind, content)
+    }
+
+    fn visit_label(&mut self, kind: &CommandType, content: &LabelContent) {
+        walk_label(self, kind, content)
+    }
+
+    fn visit_information(&mut self, kind: &CommandType, content: &InformationContent) {
+        walk_information(self, kind, content)
+    }
+
+    fn visit_character_encoding(&mut self, kind: &CommandType, content: &CharacterEncodingContent) {
+        walk_character_encoding(self, kind, content)
+    }
+
+    fn visit_format(&mut self, kind: &CommandType, content: &FormatContent) {
+        walk_format(self, kind, content)
+    }
+
+    fn visit_unit(&mut self, kind: &CommandType, content: &UnitContent) {
+        walk_unit(self, kind, content)
+    }
+
+    fn visit_overlay_content(
+        &mut self,
+        kind: &CommandType,
+        overlay_type: &OverlayType,
+        content: &OverlayContent,
+    ) {
+        walk_overlay_content(self, kind, overlay_type, content)
+    }
+
+    /// Called for every attribute entry of an overlay
+    fn visit_overlay_attribute(
+        &mut self,
+        _kind: &CommandType,
+        _overlay_type: &OverlayType,
+        _attribute: &str,
+        _value: &str,
+    ) {
+    }
+
+    /// Called for every property entry of an overlay, except language and
+    /// unit system which are part of the overlay identity
+    fn visit_overlay_property(
+        &mut self,
+        _kind: &CommandType,
+        _overlay_type: &OverlayType,
+        _property: &str,
+        _value: &str,
+    ) {
+    }
+
+    /// Called for every attribute entry of an overlay without dedicated
+    /// content model, plain values are passed on to `visit_overlay_attribute`
+    fn visit_nested_attribute(
+        &mut self,
+        kind: &CommandType,
+        overlay_type: &OverlayType,
+        attribute: &str,
+        value: &NestedValue,
+    ) {
+        match value {
+            NestedValue::Value(value) => {
+                self.visit_overlay_attribute(kind, overlay_type, attribute, value)
+            }
+            value => self.visit_nested_value(kind, overlay_type, attribute, value),
+        }
+    }
+
+    /// Called for every property entry of an overlay without dedicated
+    /// content model, plain values are passed on to `visit_overlay_property`
+    fn visit_nested_property(
+        &mut self,
+        kind: &CommandType,
+        overlay_type: &OverlayType,
+        property: &str,
+        value: &NestedValue,
+    ) {
+        match value {
+            NestedValue::Value(value) => {
+                self.visit_overlay_property(kind, overlay_type, property, value)
+            }
+            value => self.visit_nested_value(kind, overlay_type, property, value),
+        }
+    }
+
+    /// Called for nested values of overlays without dedicated content model
+    fn visit_nested_value(
+        &mut self,
+        kind: &CommandType,
+        overlay_type: &OverlayType,
+        key: &str,
+        value: &NestedValue,
+    ) {
+        walk_nested_value(self, kind, overlay_type, key, value)
+    }
+}
+
+pub fn walk_ast<V: Visit + ?Sized>(visitor: &mut V, ast: &OCAAst) {
+    for (index, command) in ast.commands.iter().enumerate() {
+        visitor.visit_command(index, command);
+    }
+}
+
+pub fn walk_command<V: Visit + ?Sized>(visitor: &mut V, _index: usize, command: &Command) {
+    let kind = &command.kind;
+    match (&command.object_kind, &command.content) {
+        (_, Some(Content::CaptureBase(content))) => visitor.visit_capture_base(kind, content),
+        (_, Some(Content::Bundle(content))) => visitor.visit_bundle(kind, content),
+        (ObjectKind::Overlay(overlay_type), Some(content)) => {
+            visitor.visit_overlay(kind, overlay_type, content)
+        }
+        _ => {}
+    }
+}
+
+pub fn walk_capture_base<V: Visit + ?Sized>(
+    visitor: &mut V,
+    kind: &CommandType,
+    content: &CaptureBaseContent,
+) {
+    for (name, attr_type) in &content.attributes {
+        visitor.visit_attribute(kind, name, attr_type.as_ref());
+    }
+    if let Some(classification) = &content.classification {
+        visitor.visit_classification(kind, classification);
+    }
+    for name in &content.flagged_attributes {
+        visitor.visit_flagged_attribute(kind, name);
+    }
+}
+
+pub fn walk_overlay<V: Visit + ?Sized>(
+    visitor: &mut V,
+    kind: &CommandType,
+    overlay_type: &OverlayType,
+    content: &Content,
+) {
+    match content {
+        Content::Meta(content) => visitor.visit_meta(kind, content),
+        Content::Label(content) => visitor.visit_label(kind, content),
+        Content::Information(content) => visitor.visit_information(kind, content),
+        Content::CharacterEncoding(content) => visitor.visit_character_encoding(kind, content),
+        Content::Format(content) => visitor.visit_format(kind, content),
+        Content::Unit(content) => visitor.visit_unit(kind, content),
+        Content::Overlay(content) => visitor.visit_overlay_content(kind, overlay_type, content),
+        Content::CaptureBase(_) | Content::Bundle(_) => {}
+    }
+}
+
+pub fn walk_meta<V: Visit + ?Sized>(visitor: &mut V, kind: &CommandType, content: &MetaContent) {
+    walk_overlay_properties(visitor, kind, &OverlayType::Meta, &content.properties)
+}
+
+pub fn walk_label<V: Visit + ?Sized>(visitor: &mut V, kind: &CommandType, content: &LabelContent) {
+    walk_overlay_attributes(visitor, kind, &OverlayType::Label, &content.attributes)
+}
+
+pub fn walk_information<V: Visit + ?Sized>(
+    visitor: &mut V,
+    kind: &CommandType,
+    content: &InformationContent,
+) {
+    walk_overlay_attributes(
+        visitor,
+        kind,
+        &OverlayType::Information,
+        &content.attributes,
+    )
+}
+
+pub fn walk_character_encoding<V: Visit + ?Sized>(
+    visitor: &mut V,
+    kind: &CommandType,
+    content: &CharacterEncodingContent,
+) {
+    let overlay_type = OverlayType::CharacterEncoding;
+    walk_overlay_attributes(visitor, kind, &overlay_type, &content.attributes);
+    if let Some(default_encoding) = &content.default_encoding {
+        visitor.visit_overlay_property(kind, &overlay_type, "default_encoding", default_encoding);
+    }
+}
+
+pub fn walk_format<V: Visit + ?Sized>(
+    visitor: &mut V,
+    kind: &CommandType,
+    content: &FormatContent,
+) {
+    walk_overlay_attributes(visitor, kind, &OverlayType::Format, &content.attributes)
+}
+
+pub fn walk_unit<V: Visit + ?Sized>(visitor: &mut V, kind: &CommandType, content: &UnitContent) {
+    walk_overlay_attributes(visitor, kind, &OverlayType::Unit, &content.attributes)
+}
+
+pub fn walk_overlay_content<V: Visit + ?Sized>(
+    visitor: &mut V,
+    kind: &CommandType,
+    overlay_type: &OverlayType,
+    content: &OverlayContent,
+) {
+    for (attribute, value) in &content.attributes {
+        visitor.visit_nested_attribute(kind, overlay_type, attribute, value);
+    }
+    for (property, value) in &content.properties {
+        visitor.visit_nested_property(kind, overlay_type, property, value);
+    }
+}
+
+pub fn walk_nested_value<V: Visit + ?Sized>(
+    visitor: &mut V,
+    kind: &CommandType,
+    overlay_type: &OverlayType,
+    key: &str,
+    value: &NestedValue,
+) {
+    match value {
+        NestedValue::Object(object) => {
+            for (key, value) in object {
+                visitor.visit_nested_value(kind, overlay_type, key, value);
+            }
+        }
+        NestedValue::Array(array) => {
+            for value in array {
+                visitor.visit_nested_value(kind, overlay_type, key, value);
+            }
+        }
+        NestedValue::Value(_) | NestedValue::Reference(_) => {}
+    }
+}
+
+fn walk_overlay_attributes<V: Visit + ?Sized>(
+    visitor: &mut V,
+    kind: &CommandType,
+    overlay_type: &OverlayType,
+    attributes: &IndexMap<String, String>,
+) {
+    for (attribute, value) in attributes {
+        visitor.visit_overlay_attribute(kind, overlay_type, attribute, value);
+    }
+}
+
+fn walk_overlay_properties<V: Visit + ?Sized>(
+    visitor: &mut V,
+    kind: &CommandType,
+    overlay_type: &OverlayType,
+    properties: &IndexMap<String, String>,
+) {
+    for (property, value) in properties {
+        visitor.visit_overlay_property(kind, overlay_type, property, value);
+    }
+}
+
+/// In-place traversal of OCA AST
+pub trait VisitMut {
+    fn visit_ast_mut(&mut self, ast: &mut OCAAst) {
+        walk_ast_mut(self, ast)
+    }
+
+    fn visit_command_mut(&mut self, index: usize, command: &mut Command) {
+        walk_command_mut(self, index, command)
+    }
+
+    fn visit_capture_base_mut(&mut self, kind: &CommandType, content: &mut CaptureBaseContent) {
+        walk_capture_base_mut(self, kind, content)
+    }
+
+    fn visit_attribute_mut(
+        &mut self,
+        _kind: &CommandType,
+        _name: &str,
+        _attr_type: &mut Option<AttributeType>,
+    ) {
+    }
+
+    fn visit_classification_mut(&mut self, _kind: &CommandType, _classification: &mut String) {}
+
+    fn visit_flagged_attribute_mut(&mut self, _kind: &CommandType, _name: &mut String) {}
+
+    fn visit_bundle_mut(&mut self, _kind: &CommandType, _content: &mut BundleContent) {}
+
+    fn visit_overlay_mut(
+        &mut self,
+        kind: &CommandType,
+        overlay_type: &OverlayType,
+        content: &mut Content,
+    ) {
+        walk_overlay_mut(self, kind, overlay_type, content)
+    }
+
+    fn visit_meta_mut(&mut self, kind: &CommandType, content: &mut MetaContent) {
+        walk_overlay_properties_mut(self, kind, &OverlayType::Meta, &mut content.properties)
+    }
+
+    fn visit_label_mut(&mut self, kind: &CommandType, content: &mut LabelContent) {
+        walk_overlay_attributes_mut(self, kind, &OverlayType::Label, &mut content.attributes)
+    }
+
+    fn visit_information_mut(&mut self, kind: &CommandType, content: &mut InformationContent) {
+        walk_overlay_attributes_mut(
+            self,
+            kind,
+            &OverlayType::Information,
+            &mut content.attributes,
+        )
+    }
+
+    fn visit_character_encoding_mut(
+        &mut self,
+        kind: &CommandType,
+        content: &mut CharacterEncodingContent,
+    ) {
+        walk_character_encoding_mut(self, kind, content)
+    }
+
+    fn visit_format_mut(&mut self, kind: &CommandType, content: &mut FormatContent) {
+        walk_overlay_attributes_mut(self, kind, &OverlayType::Format, &mut content.attributes)
+    }
+
+    fn visit_unit_mut(&mut self, kind: &CommandType, content: &mut UnitContent) {
+        walk_overlay_attributes_mut(self, kind, &OverlayType::Unit, &mut content.attributes)
+    }
+
+    fn visit_overlay_content_mut(
+        &mut self,
+        kind: &CommandType,
+        overlay_type: &OverlayType,
+        content: &mut OverlayContent,
+    ) {
+        for (attribute, value) in content.attributes.iter_mut() {
+            self.visit_nested_attribute_mut(kind, overlay_type, attribute, value);
+        }
+        for (property, value) in content.properties.iter_mut() {
+            self.visit_nested_property_mut(kind, overlay_type, property, value);
+        }
+    }
+
+    fn visit_overlay_attribute_mut(
+        &mut self,
+        _kind: &CommandType,
+        _overlay_type: &OverlayType,
+        _attribute: &str,
+        _value: &mut String,
+    ) {
+    }
+
+    fn visit_overlay_property_mut(
+        &mut self,
+        _kind: &CommandType,
+        _overlay_type: &OverlayType,
+        _property: &str,
+        _value: &mut String,
+    ) {
+    }
+
+    fn visit_nested_attribute_mut(
+        &mut self,
+        kind: &CommandType,
+        overlay_type: &OverlayType,
+        attribute: &str,
+        value: &mut NestedValue,
+    ) {
+        match value {
+            NestedValue::Value(value) => {
+                self.visit_overlay_attribute_mut(kind, overlay_type, attribute, value)
+            }
+            value => self.visit_nested_value_mut(kind, overlay_type, attribute, value),
+        }
+    }
+
+    fn visit_nested_property_mut(
+        &mut self,
+        kind: &CommandType,
+        overlay_type: &OverlayType,
+        property: &str,
+        value: &mut NestedValue,
+    ) {
+        match value {
+            NestedValue::Value(value) => {
+                self.visit_overlay_property_mut(kind, overlay_type, property, value)
+            }
+            value => self.visit_nested_value_mut(kind, overlay_type, property, value),
+        }
+    }
+
+    fn visit_nested_value_mut(
+        &mut self,
+        kind: &CommandType,
+        overlay_type: &OverlayType,
+        key: &str,
+        value: &mut NestedValue,
+    ) {
+        walk_nested_value_mut(self, kind, overlay_type, key, value)
+    }
+}
+
+pub fn walk_ast_mut<V: VisitMut + ?Sized>(visitor: &mut V, ast: &mut OCAAst) {
+    for (index, command) in ast.commands.iter_mut().enumerate() {
+        visitor.visit_command_mut(index, command);
+    }
+}
+
+pub fn walk_command_mut<V: VisitMut + ?Sized>(
+    visitor: &mut V,
+    _index: usize,
+    command: &mut Command,
+) {
+    let kind = &command.kind;
+    match (&command.object_kind, &mut command.content) {
+        (_, Some(Content::CaptureBase(content))) => visitor.visit_capture_base_mut(kind, content),
+        (_, Some(Content::Bundle(content))) => visitor.visit_bundle_mut(kind, content),
+        (ObjectKind::Overlay(overlay_type), Some(content)) => {
+            visitor.visit_overlay_mut(kind, overlay_type, content)
+        }
+        _ => {}
+    }
+}
+
+pub fn walk_capture_base_mut<V: VisitMut + ?Sized>(
+    visitor: &mut V,
+    kind: &CommandType,
+    content: &mut CaptureBaseContent,
+) {
+    for (name, attr_type) in content.attributes.iter_mut() {
+        visitor.visit_attribute_mut(kind, name, attr_type);
+    }
+    if let Some(classification) = &mut content.classification {
+        visitor.visit_classification_mut(kind, classification);
+    }
+    for name in content.flagged_attributes.iter_mut() {
+        visitor.visit_flagged_attribute_mut(kind, name);
+    }
+}
+
+pub fn walk_overlay_mut<V: VisitMut + ?Sized>(
+    visitor: &mut V,
+    kind: &CommandType,
+    overlay_type: &OverlayType,
+    content: &mut Content,
+) {
+    match content {
+        Content::Meta(content) => visitor.visit_meta_mut(kind, content),
+        Content::Label(content) => visitor.visit_label_mut(kind, content),
+        Content::Information(content) => visitor.visit_information_mut(kind, content),
+        Content::CharacterEncoding(content) => visitor.visit_character_encoding_mut(kind, content),
+        Content::Format(content) => visitor.visit_format_mut(kind, content),
+        Content::Unit(content) => visitor.visit_unit_mut(kind, content),
+        Content::Overlay(content) => visitor.visit_overlay_content_mut(kind, overlay_type, content),
+        Content::CaptureBase(_) | Content::Bundle(_) => {}
+    }
+}
+
+pub fn walk_character_encoding_mut<V: VisitMut + ?Sized>(
+    visitor: &mut V,
+    kind: &CommandType,
+    content: &mut CharacterEncodingContent,
+) {
+    let overlay_type = OverlayType::CharacterEncoding;
+    walk_overlay_attributes_mut(visitor, kind, &overlay_type, &mut content.attributes);
+    if let Some(default_encoding) = &mut content.default_encoding {
+        visitor.visit_overlay_property_mut(
+            kind,
+            &overlay_type,
+            "default_encoding",
+            default_encoding,
+        );
+    }
+}
+
+pub fn walk_nested_value_mut<V: VisitMut + ?Sized>(
+    visitor: &mut V,
+    kind: &CommandType,
+    overlay_type: &OverlayType,
+    key: &str,
+    value: &mut NestedValue,
+) {
+    match value {
+        NestedValue::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                visitor.visit_nested_value_mut(kind, overlay_type, key, value);
+            }
+        }
+        NestedValue::Array(array) => {
+            for value in array.iter_mut() {
+                visitor.visit_nested_value_mut(kind, overlay_type, key, value);
+            }
+        }
+        NestedValue::Value(_) | NestedValue::Reference(_) => {}
+    }
+}
+
+fn walk_overlay_attributes_mut<V: VisitMut + ?Sized>(
+    visitor: &mut V,
+    kind: &CommandType,
+    overlay_type: &OverlayType,
+    attributes: &mut IndexMap<String, String>,
+) {
+    for (attribute, value) in attributes.iter_mut() {
+        visitor.visit_overlay_attribute_mut(kind, overlay_type, attribute, value);
+    }
+}
+
+fn walk_overlay_properties_mut<V: VisitMut + ?Sized>(
+    visitor: &mut V,
+    kind: &CommandType,
+    overlay_type: &OverlayType,
+    properties: &mut IndexMap<String, String>,
+) {
+    for (property, value) in properties.iter_mut() {
+        visitor.visit_overlay_property_mut(kind, overlay_type, property, value);
+    }
+}
+
+/// Owning transformation of OCA AST
+///
+/// Unlike `VisitMut`, folding can rename keys: attribute and property hooks
+/// return the new key together with the new value.
+pub trait Fold {
+    fn fold_ast(&mut self, ast: OCAAst) -> OCAAst {
+        fold_ast(self, ast)
+    }
+
+    fn fold_command(&mut self, command: Command) -> Command {
+        fold_command(self, command)
+    }
+
+    fn fold_capture_base(
+        &mut self,
+        kind: &CommandType,
+        content: CaptureBaseContent,
+    ) -> CaptureBaseContent {
+        fold_capture_base(self, kind, content)
+    }
+
+    fn fold_attribute(
+        &mut self,
+        _kind: &CommandType,
+        name: String,
+        attr_type: Option<AttributeType>,
+    ) -> (String, Option<AttributeType>) {
+        (name, attr_type)
+    }
+
+    fn fold_classification(&mut self, _kind: &CommandType, classification: String) -> String {
+        classification
+    }
+
+    fn fold_flagged_attribute(&mut self, _kind: &CommandType, name: String) -> String {
+        name
+    }
+
+    fn fold_bundle(&mut self, _kind: &CommandType, content: BundleContent) -> BundleContent {
+        content
+    }
+
+    fn fold_overlay(
+        &mut self,
+        kind: &CommandType,
+        overlay_type: &OverlayType,
+        content: Content,
+    ) -> Content {
+        fold_overlay(self, kind, overlay_type, content)
+    }
+
+    fn fold_meta(&mut self, kind: &CommandType, content: MetaContent) -> MetaContent {
+        MetaContent {
+            properties: fold_overlay_properties(self, kind, &OverlayType::Meta, content.properties),
+            ..content
+        }
+    }
+
+    fn fold_label(&mut self, kind: &CommandType, content: LabelContent) -> LabelContent {
+        LabelContent {
+            attributes: fold_overlay_attributes(
+                self,
+                kind,
+                &OverlayType::Label,
+                content.attributes,
+            ),
+            ..content
+        }
+    }
+
+    fn fold_information(
+        &mut self,
+        kind: &CommandType,
+        content: InformationContent,
+    ) -> InformationContent {
+        InformationContent {
+            attributes: fold_overlay_attributes(
+                self,
+                kind,
+                &OverlayType::Information,
+                content.attributes,
+            ),
+            ..content
+        }
+    }
+
+    fn fold_character_encoding(
+        &mut self,
+        kind: &CommandType,
+        content: CharacterEncodingContent,
+    ) -> CharacterEncodingContent {
+        let overlay_type = OverlayType::CharacterEncoding;
+        CharacterEncodingContent {
+            attributes: fold_overlay_attributes(self, kind, &overlay_type, content.attributes),
+            default_encoding: content.default_encoding.map(|default_encoding| {
+                self.fold_overlay_property(
+                    kind,
+                    &overlay_type,
+                    "default_encoding".to_string(),
+                    default_encoding,
+                )
+                .1
+            }),
+        }
+    }
+
+    fn fold_format(&mut self, kind: &CommandType, content: FormatContent) -> FormatContent {
+        FormatContent {
+            attributes: fold_overlay_attributes(
+                self,
+                kind,
+                &OverlayType::Format,
+                content.attributes,
+            ),
+        }
+    }
+
+    fn fold_unit(&mut self, kind: &CommandType, content: UnitContent) -> UnitContent {
+        UnitContent {
+            attributes: fold_overlay_attributes(self, kind, &OverlayType::Unit, content.attributes),
+            ..content
+        }
+    }
+
+    fn fold_overlay_content(
+        &mut self,
+        kind: &CommandType,
+        overlay_type: &OverlayType,
+        content: OverlayContent,
+    ) -> OverlayContent {
+        OverlayContent {
+            attributes: content
+                .attributes
+                .into_iter()
+                .map(|(attribute, value)| {
+                    self.fold_nested_attribute(kind, overlay_type, attribute, value)
+                })
+                .collect(),
+            properties: content
+                .properties
+                .into_iter()
+                .map(|(property, value)| {
+                    self.fold_nested_property(kind, overlay_type, property, value)
+                })
+                .collect(),
+        }
+    }
+
+    fn fold_overlay_attribute(
+        &mut self,
+        _kind: &CommandType,
+        _overlay_type: &OverlayType,
+        attribute: String,
+        value: String,
+    ) -> (String, String) {
+        (attribute, value)
+    }
+
+    fn fold_overlay_property(
+        &mut self,
+        _kind: &CommandType,
+        _overlay_type: &OverlayType,
+        property: String,
+        value: String,
+    ) -> (String, String) {
+        (property, value)
+    }
+
+    fn fold_nested_attribute(
+        &mut self,
+        kind: &CommandType,
+        overlay_type: &OverlayType,
+        attribute: String,
+        value: NestedValue,
+    ) -> (String, NestedValue) {
+        match value {
+            NestedValue::Value(value) => {
+                let (attribute, value) =
+                    self.fold_overlay_attribute(kind, overlay_type, attribute, value);
+                (attribute, NestedValue::Value(value))
+            }
+            value => {
+                let value = self.fold_nested_value(kind, overlay_type, &attribute, value);
+                (attribute, value)
+            }
+        }
+    }
+
+    fn fold_nested_property(
+        &mut self,
+        kind: &CommandType,
+        overlay_type: &OverlayType,
+        property: String,
+        value: NestedValue,
+    ) -> (String, NestedValue) {
+        match value {
+            NestedValue::Value(value) => {
+                let (property, value) =
+                    self.fold_overlay_property(kind, overlay_type, property, value);
+                (property, NestedValue::Value(value))
+            }
+            value => {
+                let value = self.fold_nested_value(kind, overlay_type, &property, value);
+                (property, value)
+            }
+        }
+    }
+
+    fn fold_nested_value(
+        &mut self,
+        kind: &CommandType,
+        overlay_type: &OverlayType,
+        key: &str,
+        value: NestedValue,
+    ) -> NestedValue {
+        fold_nested_value(self, kind, overlay_type, key, value)
+    }
+}
+
+pub fn fold_ast<F: Fold + ?Sized>(folder: &mut F, ast: OCAAst) -> OCAAst {
+    OCAAst {
+        version: ast.version,
+        commands: ast
+            .commands
+            .into_iter()
+            .map(|command| folder.fold_command(command))
+            .collect(),
+    }
+}
+
+pub fn fold_command<F: Fold + ?Sized>(folder: &mut F, command: Command) -> Command {
+    let Command {
+        kind,
+        object_kind,
+        content,
+    } = command;
+    let content = content.map(|content| match (&object_kind, content) {
+        (_, Content::CaptureBase(content)) => {
+            Content::CaptureBase(folder.fold_capture_base(&kind, content))
+        }
+        (_, Content::Bundle(content)) => Content::Bundle(folder.fold_bundle(&kind, content)),
+        (ObjectKind::Overlay(overlay_type), content) => {
+            folder.fold_overlay(&kind, overlay_type, content)
+        }
+        (_, content) => content,
+    });
+    Command {
+        kind,
+        object_kind,
+        content,
+    }
+}
+
+pub fn fold_capture_base<F: Fold + ?Sized>(
+    folder: &mut F,
+    kind: &CommandType,
+    content: CaptureBaseContent,
+) -> CaptureBaseContent {
+    CaptureBaseContent {
+        attributes: content
+            .attributes
+            .into_iter()
+            .map(|(name, attr_type)| folder.fold_attribute(kind, name, attr_type))
+            .collect(),
+        classification: content
+            .classification
+            .map(|classification| folder.fold_classification(kind, classification)),
+        flagged_attributes: content
+            .flagged_attributes
+            .into_iter()
+            .map(|name| folder.fold_flagged_attribute(kind, name))
+            .collect(),
+    }
+}
+
+pub fn fold_overlay<F: Fold + ?Sized>(
+    folder: &mut F,
+    kind: &CommandType,
+    overlay_type: &OverlayType,
+    content: Content,
+) -> Content {
+    match content {
+        Content::Meta(content) => Content::Meta(folder.fold_meta(kind, content)),
+        Content::Label(content) => Content::Label(folder.fold_label(kind, content)),
+        Content::Information(content) => {
+            Content::Information(folder.fold_information(kind, content))
+        }
+        Content::CharacterEncoding(content) => {
+            Content::CharacterEncoding(folder.fold_character_encoding(kind, content))
+        }
+        Content::Format(content) => Content::Format(folder.fold_format(kind, content)),
+        Content::Unit(content) => Content::Unit(folder.fold_unit(kind, content)),
+        Content::Overlay(content) => {
+            Content::Overlay(folder.fold_overlay_content(kind, overlay_type, content))
+        }
+        content @ (Content::CaptureBase(_) | Content::Bundle(_)) => content,
+    }
+}
+
+pub fn fold_nested_value<F: Fold + ?Sized>(
+    folder: &mut F,
+    kind: &CommandType,
+    overlay_type: &OverlayType,
+    key: &str,
+    value: NestedValue,
+) -> NestedValue {
+    match value {
+        NestedValue::Object(object) => NestedValue::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = folder.fold_nested_value(kind, overlay_type, &key, value);
+                    (key, value)
+                })
+                .collect(),
+        ),
+        NestedValue::Array(array) => NestedValue::Array(
+            array
+                .into_iter()
+                .map(|value| folder.fold_nested_value(kind, overlay_type, key, value))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+fn fold_overlay_attributes<F: Fold + ?Sized>(
+    folder: &mut F,
+    kind: &CommandType,
+    overlay_type: &OverlayType,
+    attributes: IndexMap<String, String>,
+) -> IndexMap<String, String> {
+    attributes
+        .into_iter()
+        .map(|(attribute, value)| {
+            folder.fold_overlay_attribute(kind, overlay_type, attribute, value)
+        })
+        .collect()
+}
+
+fn fold_overlay_properties<F: Fold + ?Sized>(
+    folder: &mut F,
+    kind: &CommandType,
+    overlay_type: &OverlayType,
+    properties: IndexMap<String, String>,
+) -> IndexMap<String, String> {
+    properties
+        .into_iter()
+        .map(|(property, value)| folder.fold_overlay_property(kind, overlay_type, property, value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_ast() -> OCAAst {
+        let mut ast = OCAAst::new();
+        ast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::CaptureBase,
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes: IndexMap::from([
+                    ("name".to_string(), Some(AttributeType::Text)),
+                    ("photo".to_string(), Some(AttributeType::Binary)),
+                ]),
+                ..Default::default()
+            })),
+        });
+        ast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::Label),
+            content: Some(Content::Label(LabelContent {
+                lang: "en".to_string(),
+                attributes: IndexMap::from([("name".to_string(), "Name".to_string())]),
+            })),
+        });
+        ast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::Meta),
+            content: Some(Content::Meta(MetaContent {
+                lang: "en".to_string(),
+                properties: IndexMap::from([("name".to_string(), "Passport".to_string())]),
+            })),
+        });
+        ast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::Entry),
+            content: Some(Content::Overlay(OverlayContent {
+                attributes: IndexMap::from([(
+                    "name".to_string(),
+                    NestedValue::Object(IndexMap::from([(
+                        "a".to_string(),
+                        NestedValue::Value("A".to_string()),
+                    )])),
+                )]),
+                properties: IndexMap::new(),
+            })),
+        });
+        ast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::Layout),
+            content: Some(Content::Overlay(OverlayContent {
+                attributes: IndexMap::from([(
+                    "name".to_string(),
+                    NestedValue::Value("text".to_string()),
+                )]),
+                properties: IndexMap::from([(
+                    "layout".to_string(),
+                    NestedValue::Value("elements: []".to_string()),
+                )]),
+            })),
+        });
+        ast.commands.push(Command {
+            kind: CommandType::Remove,
+            object_kind: ObjectKind::CaptureBase,
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes: IndexMap::from([("photo".to_string(), None)]),
+                ..Default::default()
+            })),
+        });
+        ast
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        commands: usize,
+        attributes: Vec<(CommandType, String)>,
+        overlay_attributes: Vec<(OverlayType, String)>,
+        properties: Vec<String>,
+        values: Vec<String>,
+    }
+
+    impl Visit for Counter {
+        fn visit_command(&mut self, index: usize, command: &Command) {
+            self.commands += 1;
+            walk_command(self, index, command)
+        }
+
+        fn visit_attribute(
+            &mut self,
+            kind: &CommandType,
+            name: &str,
+            _attr_type: Option<&AttributeType>,
+        ) {
+            self.attributes.push((kind.clone(), name.to_string()));
+        }
+
+        fn visit_overlay_attribute(
+            &mut self,
+            _kind: &CommandType,
+            overlay_type: &OverlayType,
+            attribute: &str,
+            _value: &str,
+        ) {
+            self.overlay_attributes
+                .push((overlay_type.clone(), attribute.to_string()));
+        }
+
+        fn visit_overlay_property(
+            &mut self,
+            _kind: &CommandType,
+            _overlay_type: &OverlayType,
+            property: &str,
+            _value: &str,
+        ) {
+            self.properties.push(property.to_string());
+        }
+
+        fn visit_nested_value(
+            &mut self,
+            kind: &CommandType,
+            overlay_type: &OverlayType,
+            key: &str,
+            value: &NestedValue,
+        ) {
+            self.values.push(key.to_string());
+            walk_nested_value(self, kind, overlay_type, key, value)
+        }
+    }
+
+    #[test]
+    fn test_visit() {
+        let mut counter = Counter::default();
+        counter.visit_ast(&example_ast());
+
+        assert_eq!(counter.commands, 6);
+        assert_eq!(
+            counter.attributes,
+            vec![
+                (CommandType::Add, "name".to_string()),
+                (CommandType::Add, "photo".to_string()),
+                (CommandType::Remove, "photo".to_string()),
+            ]
+        );
+        assert_eq!(
+            counter.overlay_attributes,
+            vec![
+                (OverlayType::Label, "name".to_string()),
+                (OverlayType::Layout, "name".to_string()),
+            ]
+        );
+        assert_eq!(
+            counter.properties,
+            vec!["name".to_string(), "layout".to_string()]
+        );
+        // only nested values of generic overlays are visited as such
+        assert_eq!(counter.values, vec!["name".to_string(), "a".to_string()]);
+    }
+
+    struct UppercaseLabels;
+
+    impl VisitMut for UppercaseLabels {
+        fn visit_overlay_attribute_mut(
+            &mut self,
+            _kind: &CommandType,
+            overlay_type: &OverlayType,
+            _attribute: &str,
+            value: &mut String,
+        ) {
+            if matches!(overlay_type, OverlayType::Label | OverlayType::Layout) {
+                *value = value.to_uppercase();
+            }
+        }
+    }
+
+    #[test]
+    fn test_visit_mut() {
+        let mut ast = example_ast();
+        UppercaseLabels.visit_ast_mut(&mut ast);
+
+        match &ast.commands[1].content {
+            Some(Content::Label(content)) => assert_eq!(content.attributes["name"], "NAME"),
+            _ => panic!("Expected label content"),
+        }
+        match &ast.commands[4].content {
+            Some(Content::Overlay(content)) => assert_eq!(
+                content.attributes["name"],
+                NestedValue::Value("TEXT".to_string())
+            ),
+            _ => panic!("Expected overlay content"),
+        }
+    }
+
+    struct RenameAttribute {
+        from: String,
+        to: String,
+    }
+
+    impl RenameAttribute {
+        fn rename(&self, name: String) -> String {
+            if name == self.from {
+                self.to.clone()
+            } else {
+                name
+            }
+        }
+    }
+
+    impl Fold for RenameAttribute {
+        fn fold_attribute(
+            &mut self,
+            _kind: &CommandType,
+            name: String,
+            attr_type: Option<AttributeType>,
+        ) -> (String, Option<AttributeType>) {
+            (self.rename(name), attr_type)
+        }
+
+        fn fold_overlay_attribute(
+            &mut self,
+            _kind: &CommandType,
+            _overlay_type: &OverlayType,
+            attribute: String,
+            value: String,
+        ) -> (String, String) {
+            (self.rename(attribute), value)
+        }
+    }
+
+    #[test]
+    fn test_fold() {
+        let mut rename = RenameAttribute {
+            from: "name".to_string(),
+            to: "fullName".to_string(),
+        };
+        let ast = rename.fold_ast(example_ast());
+
+        let mut counter = Counter::default();
+        counter.visit_ast(&ast);
+        assert_eq!(
+            counter.attributes[0],
+            (CommandType::Add, "fullName".to_string())
+        );
+        assert_eq!(
+            counter.overlay_attributes,
+            vec![
+                (OverlayType::Label, "fullName".to_string()),
+                (OverlayType::Layout, "fullName".to_string()),
+            ]
+        );
+        // meta properties are not attributes and stay untouched
+        assert_eq!(
+            counter.properties,
+            vec!["name".to_string(), "layout".to_string()]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! ZIP archive of OCA bundle
+//!
+//! Archive holds `meta.json` and one `<said>.json` file per capture base and
+//! overlay, written in the JSON its SAID is computed of. `meta.json` lists the members:
+//!
+//! ```json
+//! {
+//!   "bundle": "<bundle SAID>",
+//!   "root": "<capture base SAID>",
+//!   "files": { "<capture base SAID>": { "label (en)": "<overlay SAID>" } }
+//! }
+//! ```
+//!
+//! Overlays are named by type and language or unit system, names must be
+//! unique within the capture base.
+//!
+//! Entries have fixed modification time, so the same bundle always gives
+//! the same archive.
+
+use indexmap::IndexMap;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io::{Read, Seek, Write};
+use zip::{
+    result::ZipError, write::FileOptions, CompressionMethod, DateTime, ZipArchive, ZipWriter,
+};
+
+use crate::{
+    bundle::{to_json, Bundle, CaptureBase, Overlay},
+    ocafile::error::Error,
+};
+
+const META_FILE: &str = "meta.json";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Meta {
+    bundle: String,
+    root: String,
+    /// Overlays of every capture base, by their names
+    files: IndexMap<String, IndexMap<String, String>>,
+}
+
+/// Write bundle as ZIP archive
+pub fn export_zip<W: Write + Seek>(bundle: &Bundle, writer: W) -> Result<W, Error> {
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .last_modified_time(DateTime::default());
+    let mut zip = ZipWriter::new(writer);
+
+    let mut overlays = IndexMap::new();
+    for overlay in &bundle.overlays {
+        let name = member_name(overlay);
+        if let Some(other) = overlays.insert(name.clone(), overlay.said.clone()) {
+            return Err(Error::ArchiveError(format!(
+                "overlays {} and {} have the same name {:?}",
+                other, overlay.said, name
+            )));
+        }
+    }
+    let meta = Meta {
+        bundle: bundle.said.clone(),
+        root: bundle.capture_base.said.clone(),
+        files: IndexMap::from([(bundle.capture_base.said.clone(), overlays)]),
+    };
+    let meta =
+        serde_json::to_string_pretty(&meta).map_err(|e| Error::ArchiveError(e.to_string()))?;
+    write_member(&mut zip, META_FILE, &meta, options)?;
+    write_member(
+        &mut zip,
+        &file_name(&bundle.capture_base.said),
+        &to_json(&bundle.capture_base)?,
+        options,
+    )?;
+    for overlay in &bundle.overlays {
+        write_member(
+            &mut zip,
+            &file_name(&overlay.said),
+            &to_json(overlay)?,
+            options,
+        )?;
+    }
+    zip.finish().map_err(archive_error)
+}
+
+/// Read bundle from ZIP archive, verifying SAIDs of all its members
+pub fn import_zip<R: Read + Seek>(reader: R) -> Result<Bundle, Error> {
+    let mut zip = ZipArchive::new(reader).map_err(archive_error)?;
+    let meta: Meta = read_member(&mut zip, META_FILE)?;
+
+    let capture_base: CaptureBase = read_said_member(&mut zip, &meta.root)?;
+    let overlays = meta
+        .files
+        .get(&meta.root)
+        .ok_or_else(|| Error::ArchiveError(format!("no files of root {}", meta.root)))?
+        .values()
+        .map(|said| read_said_member::<Overlay, _>(&mut zip, said))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let bundle = Bundle {
+        said: meta.bundle,
+        capture_base,
+        overlays,
+    };
+    bundle.verify()?;
+    Ok(bundle)
+}
+
+/// Name of overlay in `meta.json`, e.g. `label (en)` or `unit (si)`
+fn member_name(overlay: &Overlay) -> String {
+    let name = overlay
+        .overlay_type
+        .trim_start_matches("spec/overlays/")
+        .trim_end_matches("/1.0");
+    match ["language", "metric_system"]
+        .iter()
+        .find_map(|key| overlay.content.get(*key).and_then(|value| value.as_str()))
+    {
+        Some(lang) => format!("{} ({})", name, lang),
+        None => name.to_string(),
+    }
+}
+
+fn file_name(said: &str) -> String {
+    format!("{}.json", said)
+}
+
+fn write_member<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    name: &str,
+    content: &str,
+    options: FileOptions,
+) -> Result<(), Error> {
+    zip.start_file(name, options).map_err(archive_error)?;
+    zip.write_all(content.as_bytes())
+        .map_err(|e| Error::ArchiveError(e.to_string()))
+}
+
+fn read_member<T: DeserializeOwned, R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<T, Error> {
+    let file = zip.by_name(name).map_err(|e| match e {
+        ZipError::FileNotFound => Error::ArchiveError(format!("missing {} in archive", name)),
+        e => archive_error(e),
+    })?;
+    serde_json::from_reader(file)
+        .map_err(|e| Error::ArchiveError(format!("invalid {}: {}", name, e)))
+}
+
+/// Read member stored under its SAID, checking it has this SAID
+fn read_said_member<T: DeserializeOwned + Serialize, R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    said: &str,
+) -> Result<T, Error> {
+    let member: T = read_member(zip, &file_name(said))?;
+    let value = serde_json::to_value(&member).map_err(|e| Error::ArchiveError(e.to_string()))?;
+    if value["digest"] != said {
+        return Err(Error::ArchiveError(format!(
+            "{} holds object with SAID {}",
+            file_name(said),
+            value["digest"]
+        )));
+    }
+    Ok(member)
+}
+
+fn archive_error(e: ZipError) -> Error {
+    Error::ArchiveError(format!("invalid archive: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::tests::build;
+    use std::io::Cursor;
+
+    fn export(bundle: &Bundle) -> Vec<u8> {
+        export_zip(bundle, Cursor::new(Vec::new()))
+            .unwrap()
+            .into_inner()
+    }
+
+    #[test]
+    fn test_zip_round_trip() {
+        let bundle = build(
+            r#"
+ADD ATTRIBUTE documentNumber=Text photo=Binary
+ADD LABEL en ATTRS documentNumber="Document number" photo="Photo"
+ADD LABEL pl ATTRS documentNumber="Numer dokumentu" photo="Zdjęcie"
+ADD CHARACTER_ENCODING ATTRS photo=base64
+"#,
+        );
+        let archive = export(&bundle);
+        assert_eq!(export(&bundle), archive);
+
+        let mut zip = ZipArchive::new(Cursor::new(archive.clone())).unwrap();
+        let meta: Meta = read_member(&mut zip, META_FILE).unwrap();
+        assert_eq!(meta.root, bundle.capture_base.said);
+        assert_eq!(
+            meta.files[&meta.root].keys().collect::<Vec<_>>(),
+            vec!["character_encoding", "label (en)", "label (pl)"]
+        );
+        assert_eq!(zip.len(), 5);
+
+        assert_eq!(import_zip(Cursor::new(archive)).unwrap(), bundle);
+    }
+
+    #[test]
+    fn test_unique_member_names() {
+        let bundle = build(
+            r#"
+ADD ATTRIBUTE height=Numeric weight=Numeric
+ADD UNIT si ATTRS height=cm
+ADD UNIT imperial ATTRS weight=lb
+"#,
+        );
+        let archive = export(&bundle);
+        let mut zip = ZipArchive::new(Cursor::new(archive.clone())).unwrap();
+        let meta: Meta = read_member(&mut zip, META_FILE).unwrap();
+        assert_eq!(
+            meta.files[&meta.root].keys().collect::<Vec<_>>(),
+            vec!["unit (imperial)", "unit (si)"]
+        );
+        assert_eq!(import_zip(Cursor::new(archive)).unwrap(), bundle);
+
+        let mut duplicated = build("ADD ATTRIBUTE name=Text\nADD LABEL en ATTRS name=Name\n");
+        let mut other = duplicated.overlays[0].clone();
+        other.said = "other".to_string();
+        duplicated.overlays.push(other);
+        assert!(matches!(
+            export_zip(&duplicated, Cursor::new(Vec::new())),
+            Err(Error::ArchiveError(message)) if message.contains("\"label (en)\"")
+        ));
+    }
+
+    #[test]
+    fn test_import_verifies_members() {
+        let bundle = build("ADD ATTRIBUTE name=Text\nADD LABEL en ATTRS name=Name\n");
+
+        let mut tampered = bundle.clone();
+        tampered.overlays[0].content.insert(
+            "attribute_labels".to_string(),
+            serde_json::json!({ "name": "Surname" }),
+        );
+        let archive = export(&tampered);
+        assert!(import_zip(Cursor::new(archive)).is_err());
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let meta = Meta {
+            bundle: bundle.said.clone(),
+            root: bundle.capture_base.said.clone(),
+            files: IndexMap::from([(bundle.capture_base.said.clone(), IndexMap::new())]),
+        };
+        write_member(
+            &mut zip,
+            META_FILE,
+            &serde_json::to_string(&meta).unwrap(),
+            FileOptions::default(),
+        )
+        .unwrap();
+        let archive = zip.finish().unwrap().into_inner();
+        assert!(matches!(
+            import_zip(Cursor::new(archive)),
+            Err(Error::ArchiveError(message)) if message.contains("missing")
+        ));
+    }
+}
@@ -0,0 +1,39 @@
+use clap::Parser as ClapParser;
+use log::info;
+use ocafile::repository::Repository;
+use ocafile::server::Server;
+
+/// OCA repository server, API is described in openapi.yaml
+#[derive(clap::Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Address to listen on
+    #[arg(short, long, default_value = "127.0.0.1:8080")]
+    address: String,
+    /// Path of the repository of bundles
+    #[arg(short, long, default_value = "repository")]
+    repository: String,
+}
+
+fn main() {
+    env_logger::init();
+
+    let args = Args::parse();
+
+    let repository = match Repository::open(&args.repository) {
+        Ok(repository) => repository,
+        Err(e) => {
+            eprintln!("Can't open repository {}: {}", args.repository, e);
+            std::process::exit(1);
+        }
+    };
+    let http = match tiny_http::Server::http(&args.address) {
+        Ok(http) => http,
+        Err(e) => {
+            eprintln!("Can't listen on {}: {}", args.address, e);
+            std::process::exit(1);
+        }
+    };
+    info!("Serving {} on {}", args.repository, args.address);
+    Server::new(repository).serve(&http);
+}
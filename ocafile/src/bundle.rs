@@ -0,0 +1,816 @@
+//! OCA bundle built from OCA AST
+//!
+//! The AST is reduced into its effective state first, which is turned into
+//! oca-rs capture base and overlays and signed by oca-rs: every part gets its
+//! SAID, overlays pointing to the capture base by its SAID. The bundle SAID
+//! is computed last, over the whole bundle, the same way.
+//!
+//! SAID of a part is the digest of its compact JSON, with fields in the order
+//! oca-rs writes them and `digest` replaced by `#` placeholder of the SAID
+//! length. With the default Blake3-256 these are the SAIDs oca-rs gives, with
+//! other digests, see `digest`, the parts are signed again the same way.
+//! oca-rs has no SAID of the whole bundle, bundles read without one get it
+//! computed, with their overlays put in canonical order first.
+//!
+//! Builds are reproducible: oca-rs keeps attributes and maps of overlays
+//! sorted, flagged attributes and overlays are put in canonical order here
+//! and nothing depends on time of the build, so the same OCAfile always gives
+//! the same bytes.
+//!
+//! oca-rs requires default character encoding, so `utf-8` is written unless
+//! the OCAfile gives one, and knows only `base64`, `utf-8` and `iso-8859-1`.
+//! Overlays without content model are written with the fields the AST gives.
+//!
+//! Meta properties and properties of overlays without content model are
+//! written next to the overlay's own `digest`, `type` and `capture_base`, so
+//! properties with those names are rejected, as the
+//! `no-reserved-properties` validation rule reports.
+
+use indexmap::IndexMap;
+use oca_rs::state::{
+    encoding::Encoding,
+    oca::{overlay, DynOverlay, OCABuilder, OCATranslation},
+};
+use ocaast::{
+    ast::{
+        AttributeType, CharacterEncodingContent, Content, FormatContent, InformationContent,
+        LabelContent, MetaContent, NestedValue, OCAAst, OverlayContent, OverlayType, UnitContent,
+    },
+    state::{reserved_property_keys, OCAState, OverlayKey},
+};
+use said::derivation::SelfAddressing;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, str::FromStr};
+
+use crate::{
+    digest::said_digest,
+    ocafile::error::Error,
+    resolver::{base_state, BundleResolver},
+};
+
+pub const CAPTURE_BASE_TYPE: &str = "spec/capture_base/1.0";
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct CaptureBase {
+    #[serde(rename = "type")]
+    pub schema_type: String,
+    #[serde(rename = "digest")]
+    pub said: String,
+    pub classification: String,
+    /// Attribute names with names of their types
+    pub attributes: IndexMap<String, String>,
+    pub flagged_attributes: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Overlay {
+    /// SAID of capture base the overlay belongs to
+    pub capture_base: String,
+    #[serde(rename = "digest")]
+    pub said: String,
+    /// Overlay type like `spec/overlays/label/1.0`
+    #[serde(rename = "type")]
+    pub overlay_type: String,
+    /// Overlay specific fields, e.g. `language` and `attribute_labels`
+    #[serde(flatten)]
+    pub content: IndexMap<String, Value>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(try_from = "UnsignedBundle")]
+pub struct Bundle {
+    #[serde(rename = "digest")]
+    pub said: String,
+    pub capture_base: CaptureBase,
+    pub overlays: Vec<Overlay>,
+}
+
+/// Bundle as read, possibly without SAID of its own like oca-rs writes it
+#[derive(Deserialize)]
+struct UnsignedBundle {
+    #[serde(rename = "digest")]
+    said: Option<String>,
+    capture_base: CaptureBase,
+    overlays: Vec<Overlay>,
+}
+
+impl TryFrom<UnsignedBundle> for Bundle {
+    type Error = Error;
+
+    fn try_from(unsigned: UnsignedBundle) -> Result<Self, Error> {
+        let mut bundle = Bundle {
+            said: unsigned.said.clone().unwrap_or_default(),
+            capture_base: unsigned.capture_base,
+            overlays: unsigned.overlays,
+        };
+        if unsigned.said.is_none() {
+            sort_overlays(&mut bundle.overlays);
+            bundle.said = compute_said(&bundle, &said_digest(&bundle.capture_base.said)?)?;
+        }
+        Ok(bundle)
+    }
+}
+
+impl Bundle {
+    /// Build bundle from AST, computing SAIDs of all its parts
+    pub fn from_ast(ast: &OCAAst) -> Result<Self, Error> {
+        Self::from_state(&OCAState::from_ast(ast))
+    }
+
+    pub fn from_state(state: &OCAState) -> Result<Self, Error> {
+        Self::from_state_with_digest(state, &SelfAddressing::Blake3_256)
+    }
+
+    /// Build bundle with SAIDs computed by given digest algorithm
+    pub fn from_state_with_digest(
+        state: &OCAState,
+        derivation: &SelfAddressing,
+    ) -> Result<Self, Error> {
+        // capture base of oca-rs can't be named, only taken from its builder
+        let mut oca = OCABuilder::new(Encoding::Utf8).oca;
+        oca.capture_base.classification = state.classification.clone().unwrap_or_default();
+        oca.capture_base.attributes = state
+            .attributes
+            .iter()
+            .map(|(name, attr_type)| (name.clone(), attr_type.to_string()))
+            .collect();
+        oca.capture_base.flagged_attributes = {
+            let mut flagged_attributes = state.flagged_attributes.clone();
+            flagged_attributes.sort();
+            flagged_attributes
+        };
+        oca.overlays.clear();
+        let mut overlays = Vec::new();
+        for (key, content) in &state.overlays {
+            if let Some(reserved) = reserved_property_keys(content).first() {
+                return Err(Error::BundleError(format!(
+                    "{:?} is reserved and can't be a property of {} overlay",
+                    reserved, key
+                )));
+            }
+            match content {
+                // signed together with the others below
+                Content::Overlay(content) => overlays.push(Overlay {
+                    capture_base: String::new(),
+                    said: String::new(),
+                    overlay_type: overlay_type_name(&key.overlay_type),
+                    content: overlay_content(content),
+                }),
+                content => oca.overlays.push(oca_overlay(content)?),
+            }
+        }
+        oca.capture_base.sign();
+        for overlay in oca.overlays.iter_mut() {
+            overlay.sign(&oca.capture_base.said);
+        }
+
+        let mut capture_base: CaptureBase = from_oca(&oca.capture_base)?;
+        for overlay in &oca.overlays {
+            overlays.push(from_oca(overlay)?);
+        }
+        let resign = *derivation != SelfAddressing::Blake3_256;
+        if resign {
+            capture_base.said = compute_said(&capture_base, derivation)?;
+        }
+        for overlay in overlays.iter_mut() {
+            if resign || overlay.said.is_empty() {
+                overlay.capture_base = capture_base.said.clone();
+                overlay.said = compute_said(overlay, derivation)?;
+            }
+        }
+        sort_overlays(&mut overlays);
+
+        let mut bundle = Bundle {
+            said: String::new(),
+            capture_base,
+            overlays,
+        };
+        bundle.said = compute_said(&bundle, derivation)?;
+        Ok(bundle)
+    }
+
+    /// Build bundle from AST on top of the bundle its FROM command refers to
+    pub fn from_ast_resolved(ast: &OCAAst, resolver: &dyn BundleResolver) -> Result<Self, Error> {
+        Self::from_ast_resolved_with_digest(ast, resolver, &SelfAddressing::Blake3_256)
+    }
+
+    /// Build bundle from AST on top of the bundle its FROM command refers to,
+    /// with SAIDs computed by given digest algorithm
+    pub fn from_ast_resolved_with_digest(
+        ast: &OCAAst,
+        resolver: &dyn BundleResolver,
+        derivation: &SelfAddressing,
+    ) -> Result<Self, Error> {
+        let mut state = base_state(ast, resolver)?;
+        for command in &ast.commands {
+            state.apply(command);
+        }
+        Self::from_state_with_digest(&state, derivation)
+    }
+
+    /// Serialize bundle into compact JSON, the form its SAID is computed of
+    pub fn to_json(&self) -> Result<String, Error> {
+        to_json(self)
+    }
+
+    /// SAIDs of other bundles the overlays refer to, like code tables of
+    /// entry codes or targets of references
+    ///
+    /// Only overlays without dedicated content model can refer to bundles,
+    /// any SAID among their values is taken as such reference.
+    pub fn references(&self) -> Vec<String> {
+        let mut references = Vec::new();
+        for overlay in &self.overlays {
+            let has_content_model = parse_overlay_type_name(&overlay.overlay_type)
+                .map(|overlay_type| has_content_model(&overlay_type))
+                .unwrap_or(true);
+            if !has_content_model {
+                for value in overlay.content.values() {
+                    collect_saids(value, &mut references);
+                }
+            }
+        }
+        references.retain(|said| *said != self.said);
+        references
+    }
+
+    /// Check SAIDs of the bundle and all its parts
+    pub fn verify(&self) -> Result<(), Error> {
+        verify_said(&self.capture_base, &self.capture_base.said)?;
+        for overlay in &self.overlays {
+            verify_said(overlay, &overlay.said)?;
+            if overlay.capture_base != self.capture_base.said {
+                return Err(Error::BundleError(format!(
+                    "overlay {} belongs to other capture base {}",
+                    overlay.said, overlay.capture_base
+                )));
+            }
+        }
+        verify_said(self, &self.said)
+    }
+
+    /// State described by the bundle, reverse of `from_state`
+    pub fn to_state(&self) -> Result<OCAState, Error> {
+        let mut state = OCAState::new();
+        for (name, attr_type) in &self.capture_base.attributes {
+            let attr_type = AttributeType::from_str(attr_type).map_err(|_| {
+                Error::BundleError(format!("invalid type {:?} of {:?}", attr_type, name))
+            })?;
+            state.attributes.insert(name.clone(), attr_type);
+        }
+        if !self.capture_base.classification.is_empty() {
+            state.classification = Some(self.capture_base.classification.clone());
+        }
+        state.flagged_attributes = self.capture_base.flagged_attributes.clone();
+        for overlay in &self.overlays {
+            let overlay_type = parse_overlay_type_name(&overlay.overlay_type)?;
+            let content = content_from_overlay(&overlay_type, overlay.content.clone())?;
+            state
+                .overlays
+                .insert(OverlayKey::new(&overlay_type, &content), content);
+        }
+        Ok(state)
+    }
+}
+
+/// Type of overlay as written in bundle, e.g. `spec/overlays/character_encoding/1.0`
+pub fn overlay_type_name(overlay_type: &OverlayType) -> String {
+    let mut name = String::new();
+    for (i, c) in overlay_type.to_string().chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            name.push('_');
+        }
+        name.extend(c.to_lowercase());
+    }
+    format!("spec/overlays/{}/1.0", name)
+}
+
+/// Overlay type of its name in bundle, reverse of `overlay_type_name`
+pub fn parse_overlay_type_name(name: &str) -> Result<OverlayType, Error> {
+    let unknown = || Error::BundleError(format!("unknown overlay type {:?}", name));
+    let snake_case = name
+        .strip_prefix("spec/overlays/")
+        .and_then(|name| name.strip_suffix("/1.0"))
+        .ok_or_else(unknown)?;
+    let camel_case: String = snake_case
+        .split('_')
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+        })
+        .collect();
+    OverlayType::from_str(&camel_case).map_err(|_| unknown())
+}
+
+pub(crate) fn has_content_model(overlay_type: &OverlayType) -> bool {
+    matches!(
+        overlay_type,
+        OverlayType::Meta
+            | OverlayType::Label
+            | OverlayType::Information
+            | OverlayType::CharacterEncoding
+            | OverlayType::Format
+            | OverlayType::Unit
+    )
+}
+
+/// Put overlays in canonical order, by type, then language or unit system
+fn sort_overlays(overlays: &mut [Overlay]) {
+    overlays.sort_by_cached_key(|overlay| {
+        let qualifier = |field: &str| {
+            overlay
+                .content
+                .get(field)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        (
+            overlay.overlay_type.clone(),
+            qualifier("language"),
+            qualifier("metric_system"),
+        )
+    });
+}
+
+/// Part of bundle as oca-rs serializes it
+fn from_oca<T: Serialize + ?Sized, P: serde::de::DeserializeOwned>(part: &T) -> Result<P, Error> {
+    serde_json::to_value(part)
+        .and_then(serde_json::from_value)
+        .map_err(|e| Error::BundleError(e.to_string()))
+}
+
+/// oca-rs overlay of content with content model
+fn oca_overlay(content: &Content) -> Result<DynOverlay, Error> {
+    let overlay: DynOverlay = match content {
+        Content::Meta(content) => {
+            let mut translation = OCATranslation::new();
+            for (key, value) in &content.properties {
+                match key.as_str() {
+                    "name" => translation.add_name(value.clone()),
+                    "description" => translation.add_description(value.clone()),
+                    _ => translation.add_extra(key.clone(), value.clone()),
+                };
+            }
+            overlay::Meta::new(content.lang.clone(), &translation)
+        }
+        Content::Label(content) => {
+            let mut label = overlay::Label::new(content.lang.clone());
+            label.attribute_labels = sorted(&content.attributes);
+            label
+        }
+        Content::Information(content) => {
+            let mut information = overlay::Information::new(content.lang.clone());
+            information.attribute_information = sorted(&content.attributes);
+            information
+        }
+        Content::CharacterEncoding(content) => {
+            let default_encoding = match &content.default_encoding {
+                Some(encoding) => oca_encoding(encoding)?,
+                None => Encoding::Utf8,
+            };
+            let mut character_encoding = overlay::CharacterEncoding::new(&default_encoding);
+            character_encoding.attribute_character_encoding = content
+                .attributes
+                .iter()
+                .map(|(attribute, encoding)| Ok((attribute.clone(), oca_encoding(encoding)?)))
+                .collect::<Result<_, Error>>()?;
+            character_encoding
+        }
+        Content::Format(content) => {
+            let mut format = overlay::Format::new();
+            format.attribute_formats = sorted(&content.attributes);
+            format
+        }
+        Content::Unit(content) => {
+            let mut unit = overlay::Unit::new(content.system.clone());
+            unit.attribute_units = sorted(&content.attributes);
+            unit
+        }
+        Content::Overlay(_) | Content::CaptureBase(_) | Content::Bundle(_) => {
+            return Err(Error::BundleError(
+                "content without content model has no oca-rs overlay".to_string(),
+            ))
+        }
+    };
+    Ok(overlay)
+}
+
+fn oca_encoding(encoding: &str) -> Result<Encoding, Error> {
+    serde_json::from_value(Value::from(encoding.to_lowercase())).map_err(|_| {
+        Error::BundleError(format!(
+            "character encoding {:?} is not supported by oca-rs, expected one of base64, utf-8, iso-8859-1",
+            encoding
+        ))
+    })
+}
+
+fn sorted(map: &IndexMap<String, String>) -> BTreeMap<String, String> {
+    map.iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+fn collect_saids(value: &Value, saids: &mut Vec<String>) {
+    match value {
+        Value::String(value) if said_digest(value).is_ok() && !saids.contains(value) => {
+            saids.push(value.clone())
+        }
+        Value::Array(array) => array.iter().for_each(|value| collect_saids(value, saids)),
+        Value::Object(object) => object
+            .values()
+            .for_each(|value| collect_saids(value, saids)),
+        _ => {}
+    }
+}
+
+/// Fields of overlay without content model
+fn overlay_content(content: &OverlayContent) -> IndexMap<String, Value> {
+    let mut fields: IndexMap<String, Value> = content
+        .properties
+        .iter()
+        .map(|(key, value)| (key.clone(), nested_value(value)))
+        .collect();
+    if !content.attributes.is_empty() {
+        fields.insert(
+            "attributes".to_string(),
+            Value::Object(
+                content
+                    .attributes
+                    .iter()
+                    .map(|(key, value)| (key.clone(), nested_value(value)))
+                    .collect(),
+            ),
+        );
+    }
+    fields
+}
+
+pub(crate) fn content_from_overlay(
+    overlay_type: &OverlayType,
+    mut fields: IndexMap<String, Value>,
+) -> Result<Content, Error> {
+    let content = match overlay_type {
+        OverlayType::Meta => {
+            // oca-rs writes name and description even when they are not given
+            for key in ["name", "description"] {
+                if fields.get(key) == Some(&Value::from("")) {
+                    fields.shift_remove(key);
+                }
+            }
+            Content::Meta(MetaContent {
+                lang: take_string(&mut fields, "language")?,
+                properties: std::mem::take(&mut fields)
+                    .into_iter()
+                    .map(|(key, value)| Ok((key.clone(), into_string(&key, value)?)))
+                    .collect::<Result<_, Error>>()?,
+            })
+        }
+        OverlayType::Label => {
+            // categories are not supported, only their empty fields oca-rs writes
+            for (key, empty) in [
+                ("attribute_categories", Value::Array(vec![])),
+                ("category_labels", Value::Object(Default::default())),
+            ] {
+                if fields.get(key) == Some(&empty) {
+                    fields.shift_remove(key);
+                }
+            }
+            Content::Label(LabelContent {
+                lang: take_string(&mut fields, "language")?,
+                attributes: take_string_map(&mut fields, "attribute_labels")?,
+            })
+        }
+        OverlayType::Information => Content::Information(InformationContent {
+            lang: take_string(&mut fields, "language")?,
+            attributes: take_string_map(&mut fields, "attribute_information")?,
+        }),
+        OverlayType::CharacterEncoding => Content::CharacterEncoding(CharacterEncodingContent {
+            default_encoding: match fields.shift_remove("default_character_encoding") {
+                Some(value) => Some(into_string("default_character_encoding", value)?),
+                None => None,
+            },
+            attributes: take_string_map(&mut fields, "attribute_character_encoding")?,
+        }),
+        OverlayType::Format => Content::Format(FormatContent {
+            attributes: take_string_map(&mut fields, "attribute_formats")?,
+        }),
+        OverlayType::Unit => Content::Unit(UnitContent {
+            system: take_string(&mut fields, "metric_system")?,
+            attributes: take_string_map(&mut fields, "attribute_units")?,
+        }),
+        _ => {
+            let attributes = match fields.shift_remove("attributes") {
+                Some(Value::Object(attributes)) => attributes
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, from_value(value)?)))
+                    .collect::<Result<_, Error>>()?,
+                Some(_) => return Err(Error::BundleError("attributes must be an object".into())),
+                None => IndexMap::new(),
+            };
+            Content::Overlay(OverlayContent {
+                attributes,
+                properties: std::mem::take(&mut fields)
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, from_value(value)?)))
+                    .collect::<Result<_, Error>>()?,
+            })
+        }
+    };
+    match fields.keys().next() {
+        Some(key) => Err(Error::BundleError(format!(
+            "unexpected {:?} in {} overlay",
+            key, overlay_type
+        ))),
+        None => Ok(content),
+    }
+}
+
+fn into_string(key: &str, value: Value) -> Result<String, Error> {
+    match value {
+        Value::String(value) => Ok(value),
+        _ => Err(Error::BundleError(format!("{:?} must be a string", key))),
+    }
+}
+
+fn take_string(fields: &mut IndexMap<String, Value>, key: &str) -> Result<String, Error> {
+    let value = fields
+        .shift_remove(key)
+        .ok_or_else(|| Error::BundleError(format!("missing {:?}", key)))?;
+    into_string(key, value)
+}
+
+fn take_string_map(
+    fields: &mut IndexMap<String, Value>,
+    key: &str,
+) -> Result<IndexMap<String, String>, Error> {
+    match fields.shift_remove(key) {
+        Some(Value::Object(map)) => map
+            .into_iter()
+            .map(|(key, value)| Ok((key.clone(), into_string(&key, value)?)))
+            .collect(),
+        Some(_) => Err(Error::BundleError(format!("{:?} must be an object", key))),
+        None => Ok(IndexMap::new()),
+    }
+}
+
+fn from_value(value: Value) -> Result<NestedValue, Error> {
+    match value {
+        Value::String(value) => Ok(NestedValue::Value(value)),
+        Value::Array(array) => Ok(NestedValue::Array(
+            array
+                .into_iter()
+                .map(from_value)
+                .collect::<Result<_, _>>()?,
+        )),
+        Value::Object(object) => Ok(NestedValue::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| Ok((key, from_value(value)?)))
+                .collect::<Result<_, Error>>()?,
+        )),
+        value => Err(Error::BundleError(format!(
+            "unsupported value {} in overlay",
+            value
+        ))),
+    }
+}
+
+fn nested_value(value: &NestedValue) -> Value {
+    match value {
+        NestedValue::Value(value) | NestedValue::Reference(value) => Value::from(value.clone()),
+        NestedValue::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, value)| (key.clone(), nested_value(value)))
+                .collect(),
+        ),
+        NestedValue::Array(array) => Value::Array(array.iter().map(nested_value).collect()),
+    }
+}
+
+/// Serialize bundle or its part into compact JSON, keeping order of fields
+pub fn to_json<T: Serialize>(object: &T) -> Result<String, Error> {
+    serde_json::to_string(object).map_err(|e| Error::BundleError(e.to_string()))
+}
+
+/// Compute SAID of object with `digest` field holding its SAID
+///
+/// The digest is computed over compact JSON of the object, fields in their
+/// order, with `digest` replaced by a `#` placeholder of the same length as
+/// the final SAID, the way oca-rs signs capture bases and overlays.
+pub fn compute_said<T: Serialize>(
+    object: &T,
+    derivation: &SelfAddressing,
+) -> Result<String, Error> {
+    let placeholder = "#".repeat(derivation.derive(&[]).to_string().len());
+    let mut value = serde_json::to_value(object).map_err(|e| Error::BundleError(e.to_string()))?;
+    match value.as_object_mut() {
+        Some(object) => object.insert("digest".to_string(), Value::from(placeholder)),
+        None => return Err(Error::BundleError("SAID of non-object".to_string())),
+    };
+    Ok(derivation.derive(to_json(&value)?.as_bytes()).to_string())
+}
+
+/// Check SAID of object, computing it with the algorithm the SAID tells
+fn verify_said<T: Serialize>(object: &T, said: &str) -> Result<(), Error> {
+    let computed = compute_said(object, &said_digest(said)?)?;
+    if computed == said {
+        Ok(())
+    } else {
+        Err(Error::BundleError(format!(
+            "SAID mismatch: expected {}, computed {}",
+            said, computed
+        )))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::ocafile::parse_from_string;
+
+    /// Bundle built from given OCAfile
+    pub(crate) fn build(ocafile: &str) -> Bundle {
+        Bundle::from_ast(&parse_from_string(ocafile.to_string())).unwrap()
+    }
+
+    #[test]
+    fn test_bundle_from_ast() {
+        let ast = parse_from_string(
+            r#"
+ADD CLASSIFICATION "GICS:45102010"
+ADD ATTRIBUTE documentNumber=Text photo=Binary
+ADD FLAGGED_ATTRIBUTES documentNumber
+ADD META en PROPS name="Passport" description="Travel document"
+ADD LABEL en ATTRS documentNumber="Document number" photo="Photo"
+ADD LABEL pl ATTRS documentNumber="Numer dokumentu"
+ADD CHARACTER_ENCODING ATTRS photo=base64
+ADD CHARACTER_ENCODING PROPS default_encoding=utf-8
+ADD FORMAT photo=image/jpeg
+"#
+            .to_string(),
+        );
+        let bundle = Bundle::from_ast(&ast).unwrap();
+
+        assert_eq!(bundle.capture_base.classification, "GICS:45102010");
+        assert_eq!(bundle.capture_base.attributes["photo"], "Binary");
+        assert_eq!(
+            bundle.capture_base.flagged_attributes,
+            vec!["documentNumber"]
+        );
+        assert_eq!(
+            bundle
+                .overlays
+                .iter()
+                .map(|overlay| overlay.overlay_type.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "spec/overlays/character_encoding/1.0",
+                "spec/overlays/format/1.0",
+                "spec/overlays/label/1.0",
+                "spec/overlays/label/1.0",
+                "spec/overlays/meta/1.0",
+            ]
+        );
+        assert_eq!(bundle.overlays[3].content["language"], "pl");
+        let encoding = &bundle.overlays[0];
+        assert_eq!(encoding.capture_base, bundle.capture_base.said);
+        assert_eq!(encoding.content["default_character_encoding"], "utf-8");
+        assert_eq!(
+            encoding.content["attribute_character_encoding"]["photo"],
+            "base64"
+        );
+
+        assert_eq!(bundle.said.len(), 44);
+        let blake3 = SelfAddressing::Blake3_256;
+        assert_eq!(compute_said(&bundle, &blake3).unwrap(), bundle.said);
+        assert_eq!(
+            compute_said(&bundle.capture_base, &blake3).unwrap(),
+            bundle.capture_base.said
+        );
+        // the same AST always gives the same bundle
+        assert_eq!(Bundle::from_ast(&ast).unwrap(), bundle);
+        assert!(bundle.verify().is_ok());
+        let mut tampered = bundle.clone();
+        tampered.capture_base.classification = "GICS:45102020".to_string();
+        assert!(tampered.verify().is_err());
+
+        let serialized = serde_json::to_string(&bundle).unwrap();
+        assert_eq!(serde_json::from_str::<Bundle>(&serialized).unwrap(), bundle);
+
+        let state = bundle.to_state().unwrap();
+        assert_eq!(state, OCAState::from_ast(&ast));
+        assert_eq!(Bundle::from_state(&state).unwrap(), bundle);
+        assert_eq!(
+            parse_overlay_type_name("spec/overlays/character_encoding/1.0").unwrap(),
+            OverlayType::CharacterEncoding
+        );
+        assert!(parse_overlay_type_name("spec/overlays/colour/1.0").is_err());
+    }
+
+    #[test]
+    fn test_oca_rs_compatibility() {
+        // built with oca-rs `OCABuilder`, which doesn't compute a bundle SAID
+        let oca_rs: Bundle =
+            serde_json::from_str(include_str!("../fixtures/passport.oca.json")).unwrap();
+        assert_eq!(
+            oca_rs.capture_base.said,
+            "EDnMa9cZzm0VoXtVwFjYsWRqU7qLOGJIyMlVzS9KZcZM"
+        );
+        assert!(oca_rs.verify().is_ok());
+
+        let ast = parse_from_string(
+            r#"
+ADD CLASSIFICATION "GICS:45102010"
+ADD ATTRIBUTE documentNumber=Text photo=Binary height=Numeric
+ADD FLAGGED_ATTRIBUTES documentNumber
+ADD META en PROPS name="Passport" description="Travel document"
+ADD LABEL en ATTRS documentNumber="Document number" photo="Photo" height="Height"
+ADD LABEL pl ATTRS documentNumber="Numer dokumentu"
+ADD INFORMATION en ATTRS documentNumber="Unique identification number of the document"
+ADD CHARACTER_ENCODING ATTRS photo=base64
+ADD CHARACTER_ENCODING PROPS default_encoding=utf-8
+ADD FORMAT ATTRS documentNumber="[A-Z0-9]{9}" photo=image/jpeg
+ADD UNIT si ATTRS height=cm
+"#
+            .to_string(),
+        );
+        assert_eq!(Bundle::from_ast(&ast).unwrap(), oca_rs);
+    }
+
+    #[test]
+    fn test_reserved_properties() {
+        let ast = parse_from_string(
+            "ADD ATTRIBUTE name=Text\nADD META en PROPS type=x name=N\n".to_string(),
+        );
+        let error = Bundle::from_ast(&ast).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "\"type\" is reserved and can't be a property of Meta (en) overlay"
+        );
+
+        let ast = parse_from_string(
+            "ADD ATTRIBUTE name=Text\nADD META en PROPS name=N description=D\n".to_string(),
+        );
+        let bundle = Bundle::from_ast(&ast).unwrap();
+        let read: Bundle = serde_json::from_str(&bundle.to_json().unwrap()).unwrap();
+        assert_eq!(read.to_state().unwrap(), OCAState::from_ast(&ast));
+    }
+
+    #[test]
+    fn test_reproducible_build() {
+        let ocafile = r#"
+ADD ATTRIBUTE name=Text age=Numeric
+ADD FLAGGED_ATTRIBUTES name age
+ADD LABEL en ATTRS name="Name" age="Age"
+ADD LABEL pl ATTRS name="Imię" age="Wiek"
+ADD FORMAT ATTRS age="[0-9]+"
+"#;
+        let reordered = r#"
+ADD ATTRIBUTE age=Numeric name=Text
+ADD FORMAT ATTRS age="[0-9]+"
+ADD LABEL pl ATTRS age="Wiek" name="Imię"
+ADD FLAGGED_ATTRIBUTES age name
+ADD LABEL en ATTRS age="Age" name="Name"
+"#;
+        let bundle = build(ocafile);
+        let serialized = bundle.to_json().unwrap();
+        let rebuilt = build(ocafile);
+        assert_eq!(rebuilt.to_json().unwrap(), serialized);
+
+        let reordered = build(reordered);
+        assert_eq!(reordered.said, bundle.said);
+        assert_eq!(reordered.to_json().unwrap(), serialized);
+
+        let read_back: Bundle = serde_json::from_str(&serialized).unwrap();
+        assert!(read_back.verify().is_ok());
+    }
+
+    #[test]
+    fn test_build_with_digest() {
+        let ast = parse_from_string(
+            "ADD ATTRIBUTE name=Text\nADD LABEL en ATTRS name=\"Name\"\n".to_string(),
+        );
+        let state = OCAState::from_ast(&ast);
+        let blake3 = Bundle::from_state(&state).unwrap();
+        let sha3 = Bundle::from_state_with_digest(&state, &SelfAddressing::SHA3_256).unwrap();
+
+        assert!(sha3.said.starts_with('H'));
+        assert!(sha3.capture_base.said.starts_with('H'));
+        assert_ne!(sha3.said, blake3.said);
+        assert!(sha3.verify().is_ok());
+        assert_eq!(sha3.to_state().unwrap(), state);
+
+        // parts are verified by their own algorithms
+        let mut mixed = sha3.clone();
+        mixed.said = compute_said(&mixed, &SelfAddressing::SHA2_512).unwrap();
+        assert!(mixed.verify().is_ok());
+        mixed.said.replace_range(0..2, "0E");
+        assert!(mixed.verify().is_err());
+    }
+}
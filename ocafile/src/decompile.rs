@@ -0,0 +1,272 @@
+//! Conversion of OCA bundles back into OCA AST
+//!
+//! Bundle is turned into its state and every part of the state into ADD
+//! commands: attributes, classification and flagged attributes first, then
+//! one command per overlay and language. Building the AST gives the bundle
+//! back, with the same SAID.
+//!
+//! Bundle is verified first, so SAID of the OCAfile build is the one the
+//! bundle claims. Only overlays OCAfile has instructions for can be decompiled. Bundles with
+//! other overlays, e.g. entry codes, or with fields this crate does not know
+//! are rejected, naming the overlay.
+//!
+//! AST does not hold the digest algorithm, it is read from the bundle SAID
+//! and given as `# digest=` directive of the generated OCAfile, unless it is
+//! the default Blake3-256.
+
+use ocaast::{
+    ast::{
+        CaptureBaseContent, CharacterEncodingContent, Command, CommandType, Content, OCAAst,
+        ObjectKind,
+    },
+    state::OCAState,
+};
+
+use said::derivation::SelfAddressing;
+
+use crate::{
+    bundle::{content_from_overlay, has_content_model, parse_overlay_type_name, Bundle, Overlay},
+    digest::{digest_name, said_digest},
+    ocafile::{error::Error, generate_from_ast},
+};
+
+pub fn decompile(bundle: &Bundle) -> Result<OCAAst, Error> {
+    bundle.verify().map_err(|e| {
+        Error::DecompileError(format!("bundle {} doesn't verify: {}", bundle.said, e))
+    })?;
+    for overlay in &bundle.overlays {
+        check_decompilable(overlay)?;
+    }
+    Ok(decompile_state(&bundle.to_state()?))
+}
+
+/// OCAfile building given bundle, with its digest directive
+pub fn decompile_ocafile(bundle: &Bundle) -> Result<String, Error> {
+    generate_ocafile(&decompile(bundle)?, &bundle.said)
+}
+
+/// OCAfile of AST the bundle with given SAID was built from, with digest
+/// directive if the SAID was not computed with the default algorithm
+pub fn generate_ocafile(ast: &OCAAst, said: &str) -> Result<String, Error> {
+    let derivation = said_digest(said)?;
+    let mut ocafile = String::new();
+    if derivation != SelfAddressing::Blake3_256 {
+        let name = digest_name(&derivation).ok_or_else(|| {
+            Error::DecompileError(format!("digest of {} has no name OCAfile knows", said))
+        })?;
+        ocafile.push_str(&format!("# digest={}\n", name));
+    }
+    ocafile.push_str(&generate_from_ast(ast)?);
+    Ok(ocafile)
+}
+
+fn check_decompilable(overlay: &Overlay) -> Result<(), Error> {
+    let unsupported = |reason: &str| {
+        Error::DecompileError(format!(
+            "can't decompile {} overlay {}: {}",
+            overlay.overlay_type, overlay.said, reason
+        ))
+    };
+    let overlay_type =
+        parse_overlay_type_name(&overlay.overlay_type).map_err(|e| unsupported(&e.to_string()))?;
+    if !has_content_model(&overlay_type) {
+        return Err(unsupported("OCAfile has no instruction for it"));
+    }
+    content_from_overlay(&overlay_type, overlay.content.clone())
+        .map_err(|e| unsupported(&e.to_string()))?;
+    Ok(())
+}
+
+/// AST of ADD commands building given state from scratch
+pub fn decompile_state(state: &OCAState) -> OCAAst {
+    let mut ast = OCAAst::new();
+    let mut add = |object_kind: ObjectKind, content: Content| {
+        ast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind,
+            content: Some(content),
+        })
+    };
+
+    if !state.attributes.is_empty() {
+        add(
+            ObjectKind::CaptureBase,
+            Content::CaptureBase(CaptureBaseContent {
+                attributes: state
+                    .attributes
+                    .iter()
+                    .map(|(name, attr_type)| (name.clone(), Some(*attr_type)))
+                    .collect(),
+                ..Default::default()
+            }),
+        );
+    }
+    if let Some(classification) = &state.classification {
+        add(
+            ObjectKind::CaptureBase,
+            Content::CaptureBase(CaptureBaseContent {
+                classification: Some(classification.clone()),
+                ..Default::default()
+            }),
+        );
+    }
+    if !state.flagged_attributes.is_empty() {
+        add(
+            ObjectKind::CaptureBase,
+            Content::CaptureBase(CaptureBaseContent {
+                flagged_attributes: state.flagged_attributes.clone(),
+                ..Default::default()
+            }),
+        );
+    }
+
+    for (key, content) in &state.overlays {
+        let object_kind = ObjectKind::Overlay(key.overlay_type.clone());
+        match content {
+            // OCAfile gives attribute encodings and the default one in
+            // separate instructions
+            Content::CharacterEncoding(content)
+                if !content.attributes.is_empty() && content.default_encoding.is_some() =>
+            {
+                add(
+                    object_kind.clone(),
+                    Content::CharacterEncoding(CharacterEncodingContent {
+                        attributes: content.attributes.clone(),
+                        default_encoding: None,
+                    }),
+                );
+                add(
+                    object_kind,
+                    Content::CharacterEncoding(CharacterEncodingContent {
+                        attributes: Default::default(),
+                        default_encoding: content.default_encoding.clone(),
+                    }),
+                );
+            }
+            content => add(object_kind, content.clone()),
+        }
+    }
+    ast
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bundle::{compute_said, tests::build},
+        digest::digest_directive,
+        ocafile::parse_from_string,
+        remote::tests::{bundle_with_code_table, code_table},
+        resolver::InMemoryResolver,
+    };
+
+    #[test]
+    fn test_decompile() {
+        let ast = parse_from_string(
+            r#"
+ADD ATTRIBUTE documentNumber=Text photo=Binary height=Numeric
+ADD META en PROPS name="Passport"
+ADD LABEL en ATTRS documentNumber="Document number" photo="Photo"
+ADD CHARACTER_ENCODING PROPS default_encoding=utf-8
+ADD LABEL pl ATTRS documentNumber="Numer dokumentu"
+ADD CLASSIFICATION "GICS:45102010"
+ADD CHARACTER_ENCODING ATTRS photo=base64
+ADD UNIT si ATTRS height=cm
+ADD FLAGGED_ATTRIBUTES documentNumber
+"#
+            .to_string(),
+        );
+        let bundle = Bundle::from_ast(&ast).unwrap();
+
+        let ocafile = generate_from_ast(&decompile(&bundle).unwrap()).unwrap();
+        assert_eq!(
+            ocafile,
+            r#"ADD ATTRIBUTE documentNumber=Text height=Numeric photo=Binary
+ADD CLASSIFICATION "GICS:45102010"
+ADD FLAGGED_ATTRIBUTES documentNumber
+ADD CHARACTER_ENCODING ATTRS photo=base64
+ADD CHARACTER_ENCODING PROPS default_encoding=utf-8
+ADD LABEL en ATTRS documentNumber="Document number" photo=Photo
+ADD LABEL pl ATTRS documentNumber="Numer dokumentu"
+ADD META en PROPS name=Passport
+ADD UNIT si ATTRS height=cm
+"#
+        );
+        // no directive for the default digest
+        assert_eq!(decompile_ocafile(&bundle).unwrap(), ocafile);
+        let rebuilt = build(&ocafile);
+        assert_eq!(rebuilt.said, bundle.said);
+
+        let state = OCAState::from_ast(&ast);
+        let bundle = Bundle::from_state_with_digest(&state, &SelfAddressing::SHA2_256).unwrap();
+        let ocafile = decompile_ocafile(&bundle).unwrap();
+        assert!(ocafile.starts_with("# digest=SHA2-256\nADD ATTRIBUTE documentNumber=Text"));
+        let derivation = digest_directive(&ocafile).unwrap().unwrap();
+        assert_eq!(derivation, SelfAddressing::SHA2_256);
+        let rebuilt = Bundle::from_ast_resolved_with_digest(
+            &parse_from_string(ocafile),
+            &InMemoryResolver::new(),
+            &derivation,
+        )
+        .unwrap();
+        assert_eq!(rebuilt.said, bundle.said);
+    }
+
+    #[test]
+    fn test_decompile_unsupported_overlays() {
+        let bundle = bundle_with_code_table(&code_table());
+        let entry_code = &bundle.overlays[0];
+        assert_eq!(entry_code.overlay_type, "spec/overlays/entry_code/1.0");
+        assert_eq!(
+            decompile(&bundle).unwrap_err().to_string(),
+            format!(
+                "can't decompile spec/overlays/entry_code/1.0 overlay {}: OCAfile has no instruction for it",
+                entry_code.said
+            )
+        );
+
+        let mut bundle = code_table();
+        bundle.overlays[0].content.insert(
+            "attribute_categories".to_string(),
+            serde_json::json!(["_cat-1_"]),
+        );
+        assert!(decompile(&bundle)
+            .unwrap_err()
+            .to_string()
+            .starts_with(&format!("bundle {} doesn't verify: ", bundle.said)));
+        let blake3 = SelfAddressing::Blake3_256;
+        bundle.overlays[0].said = compute_said(&bundle.overlays[0], &blake3).unwrap();
+        let label = bundle.overlays[0].said.clone();
+        bundle.said = compute_said(&bundle, &blake3).unwrap();
+        assert_eq!(
+            decompile(&bundle).unwrap_err().to_string(),
+            format!(
+                "can't decompile spec/overlays/label/1.0 overlay {}: unexpected \"attribute_categories\" in Label overlay",
+                label
+            )
+        );
+    }
+
+    #[test]
+    fn test_decompile_bundle_of_other_tool() {
+        let oca_rs: Bundle =
+            serde_json::from_str(include_str!("../fixtures/passport.oca.json")).unwrap();
+        let ocafile = decompile_ocafile(&oca_rs).unwrap();
+        let rebuilt = build(&ocafile);
+        assert_eq!(rebuilt.said, oca_rs.said);
+        assert_eq!(rebuilt, oca_rs);
+
+        let mut tampered = oca_rs;
+        tampered.overlays[0]
+            .content
+            .insert("default_character_encoding".to_string(), "base64".into());
+        assert_eq!(
+            decompile(&tampered).unwrap_err().to_string(),
+            format!(
+                "bundle {} doesn't verify: {}",
+                tampered.said,
+                tampered.verify().unwrap_err()
+            )
+        );
+    }
+}
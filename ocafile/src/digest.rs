@@ -0,0 +1,105 @@
+//! Digest algorithms of SAIDs
+//!
+//! Algorithm is chosen by name when building, e.g. `--digest SHA3-256` or a
+//! `# digest=SHA3-256` directive at the top of OCAfile. When verifying, it is
+//! read from the derivation code every SAID starts with, so bundles made
+//! with any supported algorithm can be checked.
+
+use said::{derivation::SelfAddressing, prefix::SelfAddressingPrefix};
+use std::str::FromStr;
+
+use crate::ocafile::error::Error;
+
+/// Names of supported algorithms with their derivation codes
+const DIGESTS: [(&str, SelfAddressing); 6] = [
+    ("Blake3-256", SelfAddressing::Blake3_256),
+    ("SHA3-256", SelfAddressing::SHA3_256),
+    ("SHA2-256", SelfAddressing::SHA2_256),
+    ("Blake3-512", SelfAddressing::Blake3_512),
+    ("SHA3-512", SelfAddressing::SHA3_512),
+    ("SHA2-512", SelfAddressing::SHA2_512),
+];
+
+/// Algorithm of given name, matched case-insensitively
+pub fn parse_digest(name: &str) -> Result<SelfAddressing, Error> {
+    DIGESTS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(name))
+        .map(|(_, derivation)| derivation.clone())
+        .ok_or_else(|| {
+            let known: Vec<&str> = DIGESTS.iter().map(|(known, _)| *known).collect();
+            Error::BundleError(format!(
+                "unknown digest {:?}, expected one of {}",
+                name,
+                known.join(", ")
+            ))
+        })
+}
+
+pub fn digest_name(derivation: &SelfAddressing) -> Option<&'static str> {
+    DIGESTS
+        .iter()
+        .find(|(_, known)| known == derivation)
+        .map(|(name, _)| *name)
+}
+
+/// Algorithm the SAID was computed with
+pub fn said_digest(said: &str) -> Result<SelfAddressing, Error> {
+    SelfAddressingPrefix::from_str(said)
+        .map(|prefix| prefix.derivation)
+        .map_err(|_| Error::BundleError(format!("invalid SAID {:?}", said)))
+}
+
+/// Digest given by `# digest=<name>` directive
+///
+/// Directives are comments of `key=value` form at the very top of OCAfile,
+/// before any instruction, empty line or other comment.
+pub fn digest_directive(ocafile: &str) -> Result<Option<SelfAddressing>, Error> {
+    for line in ocafile.lines() {
+        let directive = line
+            .strip_prefix('#')
+            .and_then(|directive| directive.split_once('='));
+        match directive {
+            Some((key, value)) if key.trim().eq_ignore_ascii_case("digest") => {
+                return parse_digest(value.trim()).map(Some)
+            }
+            Some((key, _)) if !key.trim().contains(' ') => continue,
+            _ => break,
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest() {
+        assert_eq!(parse_digest("sha3-256").unwrap(), SelfAddressing::SHA3_256);
+        assert!(parse_digest("MD5").is_err());
+        assert_eq!(digest_name(&SelfAddressing::SHA2_256), Some("SHA2-256"));
+        let said = SelfAddressing::SHA2_256.derive(b"oca").to_string();
+        assert_eq!(said_digest(&said).unwrap(), SelfAddressing::SHA2_256);
+        assert!(said_digest("not a SAID").is_err());
+
+        assert_eq!(
+            digest_directive("# digest=SHA3-512\nADD ATTRIBUTE name=Text\n").unwrap(),
+            Some(SelfAddressing::SHA3_512)
+        );
+        assert_eq!(
+            digest_directive("# escape=\\\n# digest = SHA2-256\n").unwrap(),
+            Some(SelfAddressing::SHA2_256)
+        );
+        // directives end with the first instruction or plain comment
+        assert_eq!(
+            digest_directive("ADD ATTRIBUTE name=Text\n# digest=SHA3-256\n").unwrap(),
+            None
+        );
+        assert_eq!(
+            digest_directive("# passport schema\n# digest=SHA3-256\n").unwrap(),
+            None
+        );
+        assert!(digest_directive("# digest=MD5\n").is_err());
+    }
+}
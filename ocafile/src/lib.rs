@@ -1 +1,10 @@
-pub mod ocafile;
\ No newline at end of file
+pub mod archive;
+pub mod bundle;
+pub mod decompile;
+pub mod digest;
+pub mod ocafile;
+pub mod remote;
+pub mod repository;
+pub mod resolver;
+pub mod search;
+pub mod server;
@@ -1,8 +1,22 @@
 use std::fs;
+use std::path::PathBuf;
 
 use clap::Parser as ClapParser;
 use clap::Subcommand;
-use ocafile::ocafile::parse_from_string;
+use ocafile::archive::{export_zip, import_zip};
+use ocafile::bundle::{overlay_type_name, Bundle};
+use ocafile::decompile::decompile_ocafile;
+use ocafile::digest::{digest_directive, parse_digest};
+use ocafile::ocafile::parse_with_spans;
+use ocafile::remote::{PublishOutcome, RemoteRepository};
+use ocafile::repository::Repository;
+use ocafile::resolver::{self, BundleResolver, ChainResolver, DirectoryResolver};
+use ocafile::search::SearchQuery;
+use ocaast::ast::{OCAAst, OverlayType};
+use ocaast::coverage::coverage;
+use ocaast::state::OCAState;
+use ocaast::validator::{OCAValidator, Validator};
+use said::derivation::SelfAddressing;
 use serde_json;
 
 #[macro_use]
@@ -11,6 +25,9 @@ extern crate log;
 #[derive(clap::Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// Path of local repository of bundles [default: ~/.ocatool/repository]
+    #[arg(long, global = true)]
+    local_repository: Option<String>,
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -20,19 +37,181 @@ enum Commands {
     Build {
         #[arg(short, long)]
         file: Option<String>,
+        /// Id of validation rule to disable, can be repeated
+        #[arg(long)]
+        disable: Vec<String>,
+        /// Write also AST of OCAfile next to the bundle
+        #[arg(long)]
+        ast: bool,
+        /// Directory with bundles which FROM can refer to
+        #[arg(long, default_value = ".")]
+        bundles: String,
+        /// Expected SAID of the bundle, build fails if it differs
+        #[arg(long)]
+        verify: Option<String>,
+        /// Digest algorithm of SAIDs, overrides `# digest=` directive of
+        /// OCAfile [default: Blake3-256]
+        #[arg(long)]
+        digest: Option<String>,
+        /// Format of written bundle
+        #[arg(long, value_enum, default_value_t = BundleFormat::Json)]
+        format: BundleFormat,
     },
+    /// Validate OCAfile and print report of all findings
+    Validate {
+        #[arg(short, long)]
+        file: Option<String>,
+        /// Print report as JSON
+        #[arg(long)]
+        json: bool,
+        /// Id of validation rule to disable, can be repeated
+        #[arg(long)]
+        disable: Vec<String>,
+        /// Directory with bundles which FROM can refer to
+        #[arg(long, default_value = ".")]
+        bundles: String,
+    },
+    /// Report attributes and properties missing in some of the languages
+    Coverage {
+        #[arg(short, long)]
+        file: Option<String>,
+        /// Language which must be covered, can be repeated
+        #[arg(short, long)]
+        lang: Vec<String>,
+        /// Print report as JSON
+        #[arg(long)]
+        json: bool,
+        /// Directory with bundles which FROM can refer to
+        #[arg(long, default_value = ".")]
+        bundles: String,
+    },
+    /// Convert OCA bundle back into OCAfile
+    Decompile {
+        /// Bundle JSON file, or its ZIP archive
+        bundle: String,
+        /// Write OCAfile to given file instead of standard output
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Upload bundles of local repository, with bundles they refer to, to
+    /// OCA repository
     Publish {
+        /// URL of OCA repository
         #[arg(short, long)]
         repository: String,
+        /// SAID of bundle to publish, can be repeated
+        #[arg(required = true)]
+        said: Vec<String>,
     },
     Sign {
         #[arg(short, long)]
         scid: String,
     },
+    /// Download bundle from OCA repository into local repository
+    Fetch {
+        /// SAID of bundle to fetch
+        said: String,
+        /// URL of OCA repository, or directory with bundles
+        #[arg(short, long)]
+        repository: String,
+        /// Fetch also bundles it refers to, like its FROM base and code
+        /// tables
+        #[arg(long)]
+        transitive: bool,
+    },
+    /// List bundles of local repository
+    Ls,
+    /// Search bundles of local repository
+    Search {
+        /// Words of meta name or description
+        text: Option<String>,
+        /// Language of meta to search in
+        #[arg(short, long)]
+        lang: Option<String>,
+        #[arg(short, long)]
+        classification: Option<String>,
+        /// Attribute bundle must have, as `name` or `name=Type`, can be
+        /// repeated
+        #[arg(long)]
+        has_attr: Vec<String>,
+    },
 
 }
 
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum BundleFormat {
+    /// `<said>.ocabundle` with bundle JSON
+    Json,
+    /// `<said>.zip` archive with a file per capture base and overlay
+    Zip,
+}
+
+/// Validator with built-in rules, except the disabled ones
+fn validator(disable: &[String]) -> OCAValidator {
+    let mut validator = OCAValidator::new();
+    for id in disable {
+        if let Err(e) = validator.disable(id) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+    validator
+}
+
+fn local_repository(path: &Option<String>) -> Repository {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(".ocatool")
+            .join("repository"),
+    };
+    match Repository::open(&path) {
+        Ok(repository) => repository,
+        Err(e) => {
+            eprintln!("Can't open repository {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
 
+/// Print SAIDs of bundles with their names
+fn print_bundles(repository: &Repository, saids: Result<Vec<String>, ocafile::ocafile::error::Error>) {
+    let saids = saids.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let meta_type = overlay_type_name(&OverlayType::Meta);
+    for said in saids {
+        let name = match repository.get(&said) {
+            Ok(Some(bundle)) => bundle
+                .overlays
+                .iter()
+                .find(|overlay| overlay.overlay_type == meta_type)
+                .and_then(|meta| meta.content.get("name"))
+                .and_then(|name| name.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            _ => String::new(),
+        };
+        println!("{}\t{}", said, name);
+    }
+}
+
+/// State of the bundle OCAfile extends with FROM, looked up in the bundles
+/// directory first, then in local repository
+fn base_state(oca: &OCAAst, bundles: &str, repository: &Repository) -> OCAState {
+    let directory = DirectoryResolver::new(bundles);
+    let resolver = ChainResolver::new().with(&directory).with(repository);
+    match resolver::base_state(oca, &resolver) {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("Can't resolve FROM bundle: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
 
 /// TODO extract OCAFILE lib to seperate crate
 
@@ -43,7 +222,15 @@ fn main() {
 
 
     match &args.command {
-        Some(Commands::Build { file }) => {
+        Some(Commands::Build {
+            file,
+            disable,
+            ast,
+            bundles,
+            verify,
+            digest,
+            format,
+        }) => {
             info!("Building OCA bundle from oca file");
 
             let unparsed_file = match file {
@@ -51,31 +238,241 @@ fn main() {
                 None => fs::read_to_string("OCAfile").expect("Can't read file"),
             };
 
-            let oca = parse_from_string(unparsed_file);
-            //println!("{:#?}", oca);
-            let serialzied_ast = serde_json::to_string_pretty(&oca).unwrap();
-            //let oca_bundle = oca.generate_bundle();
-            //let serialized_oca = serde_json::to_string_pretty(&oca_bundle).unwrap();
+            let derivation = match digest {
+                Some(digest) => parse_digest(digest).map(Some),
+                None => digest_directive(&unparsed_file),
+            };
+            let derivation = match derivation {
+                Ok(derivation) => derivation.unwrap_or(SelfAddressing::Blake3_256),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
 
-            //let said = oca_bundle.said.to_string();
-            //save to file
-            fs::write("output".to_string() + ".ocabundle", serialzied_ast).expect("Unable to write file");
+            let (oca, spans) = parse_with_spans(unparsed_file);
+            let validator = validator(disable);
+            let repository = local_repository(&args.local_repository);
+            let mut state = base_state(&oca, bundles, &repository);
+            let report = validator
+                .validate_ast_from(&oca, state.clone())
+                .with_spans(&spans);
+            eprint!("{}", report);
+            if !report.is_valid() {
+                std::process::exit(1);
+            }
+            for command in &oca.commands {
+                state.apply(command);
+            }
+            let oca_bundle = match Bundle::from_state_with_digest(&state, &derivation) {
+                Ok(oca_bundle) => oca_bundle,
+                Err(e) => {
+                    eprintln!("Can't build OCA bundle: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            if let Some(expected) = verify {
+                if oca_bundle.said != *expected {
+                    eprintln!(
+                        "Build is not reproducible: expected {}, got {}",
+                        expected, oca_bundle.said
+                    );
+                    std::process::exit(1);
+                }
+            }
+            let said = oca_bundle.said.to_string();
+            match format {
+                BundleFormat::Json => {
+                    let serialized_oca = oca_bundle.to_json().unwrap();
+                    fs::write(said.clone() + ".ocabundle", serialized_oca)
+                        .expect("Unable to write file");
+                }
+                BundleFormat::Zip => {
+                    let archive = fs::File::create(said.clone() + ".zip").expect("Unable to write file");
+                    if let Err(e) = export_zip(&oca_bundle, archive) {
+                        eprintln!("Can't write OCA bundle archive: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            let stored = repository
+                .store(&oca_bundle)
+                .and_then(|_| repository.store_ast(&said, &oca));
+            if let Err(e) = stored {
+                eprintln!("Can't store OCA bundle in repository: {}", e);
+                std::process::exit(1);
+            }
+            if *ast {
+                let serialzied_ast = serde_json::to_string_pretty(&oca).unwrap();
+                fs::write(said.clone() + ".ocaast", serialzied_ast).expect("Unable to write file");
+            }
+            println!("{}", said);
 
         }
-        Some(Commands::Publish { repository: _ }) => {
-            info!("Publish OCA bundle to repository")
+        Some(Commands::Validate {
+            file,
+            json,
+            disable,
+            bundles,
+        }) => {
+            info!("Validating oca file");
+
+            let unparsed_file = match file {
+                Some(file) => fs::read_to_string(file).expect("Can't read file"),
+                None => fs::read_to_string("OCAfile").expect("Can't read file"),
+            };
+
+            let (oca, spans) = parse_with_spans(unparsed_file);
+            let validator = validator(disable);
+            let report = validator
+                .validate_ast_from(
+                    &oca,
+                    base_state(&oca, bundles, &local_repository(&args.local_repository)),
+                )
+                .with_spans(&spans);
+            if *json {
+                println!("{}", report.to_json().unwrap());
+            } else {
+                print!("{}", report);
+            }
+            if !report.is_valid() {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Coverage {
+            file,
+            lang,
+            json,
+            bundles,
+        }) => {
+            info!("Checking language coverage of oca file");
+
+            let unparsed_file = match file {
+                Some(file) => fs::read_to_string(file).expect("Can't read file"),
+                None => fs::read_to_string("OCAfile").expect("Can't read file"),
+            };
+
+            let (oca, _) = parse_with_spans(unparsed_file);
+            let mut state = base_state(&oca, bundles, &local_repository(&args.local_repository));
+            for command in &oca.commands {
+                state.apply(command);
+            }
+            let report = coverage(&state, lang);
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print!("{}", report);
+            }
+            if !report.is_complete() {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Decompile { bundle, output }) => {
+            info!("Decompiling OCA bundle into oca file");
+
+            let oca_bundle = if bundle.ends_with(".zip") {
+                let archive = fs::File::open(bundle).expect("Can't read file");
+                import_zip(archive).map_err(|e| e.to_string())
+            } else {
+                let serialized_bundle = fs::read_to_string(bundle).expect("Can't read file");
+                serde_json::from_str::<Bundle>(&serialized_bundle).map_err(|e| e.to_string())
+            };
+            let ocafile = oca_bundle
+                .and_then(|bundle| decompile_ocafile(&bundle).map_err(|e| e.to_string()));
+            let ocafile = match ocafile {
+                Ok(ocafile) => ocafile,
+                Err(e) => {
+                    eprintln!("Can't decompile OCA bundle: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            match output {
+                Some(output) => fs::write(output, ocafile).expect("Unable to write file"),
+                None => print!("{}", ocafile),
+            }
+        }
+        Some(Commands::Publish { repository, said }) => {
+            info!("Publish OCA bundle to repository");
+
+            let local = local_repository(&args.local_repository);
+            let remote = RemoteRepository::new(repository);
+            for said in said {
+                let published = local
+                    .resolve(said)
+                    .and_then(|bundle| remote.publish_with_references(&bundle, &local));
+                match published {
+                    Ok(published) => {
+                        for (said, outcome) in published {
+                            match outcome {
+                                PublishOutcome::Created => println!("{}\tpublished", said),
+                                PublishOutcome::AlreadyPublished => {
+                                    println!("{}\talready published", said)
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Can't publish {}: {}", said, e);
+                        std::process::exit(1);
+                    }
+                }
+            }
         }
         Some(Commands::Sign { scid: _ }) => {
             info!("Sign OCA bundle byc SCID")
         }
+        Some(Commands::Fetch {
+            said,
+            repository,
+            transitive,
+        }) => {
+            info!("Fetch OCA bundle from repository");
+
+            let local = local_repository(&args.local_repository);
+            let fetched = if repository.starts_with("http://") || repository.starts_with("https://")
+            {
+                local.fetch(said, &RemoteRepository::new(repository), *transitive)
+            } else {
+                local.fetch(said, &DirectoryResolver::new(repository), *transitive)
+            };
+            match fetched {
+                Ok(fetched) => {
+                    for said in fetched {
+                        println!("{}", said);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Can't fetch {}: {}", said, e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Commands::Ls) => {
+            let repository = local_repository(&args.local_repository);
+            print_bundles(&repository, repository.list());
+        }
+        Some(Commands::Search {
+            text,
+            lang,
+            classification,
+            has_attr,
+        }) => {
+            let repository = local_repository(&args.local_repository);
+            let query = SearchQuery {
+                text: text.clone(),
+                lang: lang.clone(),
+                classification: classification.clone(),
+                attributes: has_attr.clone(),
+            };
+            print_bundles(&repository, repository.search(&query));
+        }
         None => {}
     }
 
-    println!("DONE");
+    eprintln!("DONE");
 }
 
 // ocafile build -i OCAfile
 // ocafile build -s scid
 // ocafile publish
-// ocafile fetch SAI
 // ocafile inspect
\ No newline at end of file
@@ -11,4 +11,28 @@ pub enum Error {
 
     #[error("{0}")]
     UnknownError(String),
+
+    #[error("{0}")]
+    GeneratorError(String),
+
+    #[error("{0}")]
+    BundleError(String),
+
+    #[error("{0}")]
+    ArchiveError(String),
+
+    #[error("{0}")]
+    RepositoryError(String),
+
+    #[error("{0}")]
+    RemoteError(String),
+
+    #[error("repository holds other content under {0}: {1}")]
+    PublishConflict(String, String),
+
+    #[error("bundle {0} not found")]
+    BundleNotFound(String),
+
+    #[error("{0}")]
+    DecompileError(String),
 }
@@ -1,7 +1,11 @@
 use crate::ocafile::{error::Error, instructions::helpers, Pair, Rule};
 use indexmap::IndexMap;
 use log::{debug, info};
-use ocaast::ast::{Command, CommandType, Content, NestedValue, ObjectKind, OverlayType};
+use ocaast::ast::{
+    AttributeType, CaptureBaseContent, CharacterEncodingContent, Command, CommandType, Content,
+    FormatContent, InformationContent, LabelContent, MetaContent, ObjectKind, OverlayType,
+    UnitContent,
+};
 
 pub struct AddInstruction {}
 
@@ -17,27 +21,28 @@ impl AddInstruction {
             content = match object.as_rule() {
                 Rule::meta => {
                     object_kind = Some(ObjectKind::Overlay(ocaast::ast::OverlayType::Meta));
-                    helpers::extract_content(object)
+                    helpers::extract_content(object).map(|content| {
+                        Content::Meta(MetaContent {
+                            lang: content.lang.unwrap_or_default(),
+                            properties: content.properties,
+                        })
+                    })
                 }
                 Rule::attribute => {
                     object_kind = Some(ObjectKind::CaptureBase);
-                    let mut attributes: IndexMap<String, NestedValue> = IndexMap::new();
+                    let mut attributes: IndexMap<String, Option<AttributeType>> = IndexMap::new();
                     for attr_pairs in object.into_inner() {
                         match attr_pairs.as_rule() {
                             Rule::attr_pairs => {
                                 info!("attribute: {:?}", attr_pairs);
                                 for attr in attr_pairs.into_inner() {
                                     debug!("Parsing attribute {:?}", attr);
-                                    if let Some((key, value)) =
-                                        helpers::extract_attribute_key_pairs(attr)
-                                    {
-                                        debug!("Parsed attribute: {:?} = {:?}", key, value);
-
-                                        // TODO find out how to parse nested objects
-                                        attributes.insert(key, NestedValue::Value(value));
-                                    } else {
-                                        debug!("Skipping attribute");
-                                    }
+                                    let (key, attr_type) =
+                                        helpers::extract_attribute_type_pair(attr)?;
+                                    debug!("Parsed attribute: {:?} = {:?}", key, attr_type);
+
+                                    // TODO find out how to parse nested objects
+                                    attributes.insert(key, Some(attr_type));
                                 }
                             }
                             _ => {
@@ -48,54 +53,87 @@ impl AddInstruction {
                             }
                         }
                     }
-                    Some(Content {
-                        properties: None,
-                        attributes: Some(attributes),
-                    })
+                    Some(Content::CaptureBase(CaptureBaseContent {
+                        attributes,
+                        ..Default::default()
+                    }))
                 }
                 Rule::comment => continue,
                 Rule::classification => {
                     object_kind = Some(ObjectKind::CaptureBase);
-                    let mut properties: IndexMap<String, NestedValue> = IndexMap::new();
                     let classification = object.into_inner().next().unwrap();
                     print!("Classification: {:?}", classification.as_rule());
-                    properties.insert(
-                        "classification".to_string(),
-                        NestedValue::Value(classification.as_str().to_string()),
-                    );
-
-                    Some(Content {
-                        properties: Some(properties),
-                        attributes: None,
-                    })
+
+                    Some(Content::CaptureBase(CaptureBaseContent {
+                        classification: Some(helpers::extract_value(classification)),
+                        ..Default::default()
+                    }))
                 }
                 Rule::information => {
                     object_kind = Some(ObjectKind::Overlay(OverlayType::Information));
-                    helpers::extract_content(object)
-                }
-                Rule::character_encoding => {
-                    object_kind = Some(ObjectKind::Overlay(OverlayType::CharacterEncoding));
-                    helpers::extract_content(object)
+                    helpers::extract_content(object).map(|content| {
+                        Content::Information(InformationContent {
+                            lang: content.lang.unwrap_or_default(),
+                            attributes: content.attributes,
+                        })
+                    })
                 }
-                Rule::character_encoding_props => {
+                Rule::character_encoding | Rule::character_encoding_props => {
                     object_kind = Some(ObjectKind::Overlay(OverlayType::CharacterEncoding));
-                    helpers::extract_content(object)
+                    match helpers::extract_content(object) {
+                        Some(mut content) => {
+                            let default_encoding = content.properties.shift_remove("default_encoding");
+                            if let Some(key) = content.properties.keys().next() {
+                                return Err(Error::UnexpectedToken(format!(
+                                    "Unknown character encoding property {:?}",
+                                    key
+                                )));
+                            }
+                            Some(Content::CharacterEncoding(CharacterEncodingContent {
+                                attributes: content.attributes,
+                                default_encoding,
+                            }))
+                        }
+                        None => None,
+                    }
                 }
                 Rule::label => {
                     object_kind = Some(ObjectKind::Overlay(OverlayType::Label));
-                    helpers::extract_content(object)
+                    helpers::extract_content(object).map(|content| {
+                        Content::Label(LabelContent {
+                            lang: content.lang.unwrap_or_default(),
+                            attributes: content.attributes,
+                        })
+                    })
                 }
                 Rule::unit => {
                     object_kind = Some(ObjectKind::Overlay(OverlayType::Unit));
-                    helpers::extract_content(object)
+                    helpers::extract_content(object).map(|content| {
+                        Content::Unit(UnitContent {
+                            system: content.unit_system.unwrap_or_default(),
+                            attributes: content.attributes,
+                        })
+                    })
                 }
                 Rule::format => {
                     object_kind = Some(ObjectKind::Overlay(OverlayType::Format));
-                    helpers::extract_content(object)
+                    helpers::extract_content(object).map(|content| {
+                        Content::Format(FormatContent {
+                            attributes: content.attributes,
+                        })
+                    })
                 }
                 Rule::flagged_attrs => {
                     object_kind = Some(ObjectKind::CaptureBase);
-                    None
+                    let flagged_attributes = object
+                        .into_inner()
+                        .map(helpers::extract_key)
+                        .collect();
+
+                    Some(Content::CaptureBase(CaptureBaseContent {
+                        flagged_attributes,
+                        ..Default::default()
+                    }))
                 }
                 _ => {
                     return Err(Error::UnexpectedToken(format!(
@@ -112,6 +150,90 @@ impl AddInstruction {
             content: content,
         })
     }
+
+    /// Generate ADD instruction from command
+    pub(crate) fn generate(command: &Command) -> Result<String, Error> {
+        let instruction = match &command.content {
+            Some(Content::CaptureBase(content)) => {
+                // OCAfile sets one of these per instruction, more of them
+                // can't be written without changing the AST
+                let populated = [
+                    !content.attributes.is_empty(),
+                    content.classification.is_some(),
+                    !content.flagged_attributes.is_empty(),
+                ];
+                if populated.iter().filter(|populated| **populated).count() > 1 {
+                    return Err(Error::GeneratorError(
+                        "Capture base command sets more than one of attributes, classification and flagged attributes".to_string(),
+                    ));
+                }
+                if !content.attributes.is_empty() {
+                    format!(
+                        "ATTRIBUTE {}",
+                        helpers::format_attribute_types(&content.attributes)?
+                    )
+                } else if let Some(classification) = &content.classification {
+                    format!("CLASSIFICATION {}", helpers::format_value(classification))
+                } else if !content.flagged_attributes.is_empty() {
+                    format!(
+                        "FLAGGED_ATTRIBUTES {}",
+                        helpers::format_keys(content.flagged_attributes.iter())
+                    )
+                } else {
+                    return Err(Error::GeneratorError(
+                        "Empty capture base command".to_string(),
+                    ));
+                }
+            }
+            Some(Content::Meta(content)) => format!(
+                "META {} PROPS {}",
+                helpers::format_lang(&content.lang)?,
+                helpers::format_key_pairs(&content.properties)?
+            ),
+            Some(Content::Label(content)) => format!(
+                "LABEL {} ATTRS {}",
+                helpers::format_lang(&content.lang)?,
+                helpers::format_key_pairs(&content.attributes)?
+            ),
+            Some(Content::Information(content)) => format!(
+                "INFORMATION {} ATTRS {}",
+                helpers::format_lang(&content.lang)?,
+                helpers::format_key_pairs(&content.attributes)?
+            ),
+            Some(Content::CharacterEncoding(content)) => match &content.default_encoding {
+                Some(_) if !content.attributes.is_empty() => {
+                    return Err(Error::GeneratorError(
+                        "Character encoding command sets both attributes and default encoding"
+                            .to_string(),
+                    ))
+                }
+                Some(default_encoding) => format!(
+                    "CHARACTER_ENCODING PROPS default_encoding={}",
+                    helpers::format_value(default_encoding)
+                ),
+                None => format!(
+                    "CHARACTER_ENCODING ATTRS {}",
+                    helpers::format_key_pairs(&content.attributes)?
+                ),
+            },
+            Some(Content::Format(content)) => format!(
+                "FORMAT ATTRS {}",
+                helpers::format_key_pairs(&content.attributes)?
+            ),
+            Some(Content::Unit(content)) => format!(
+                "UNIT {} ATTRS {}",
+                helpers::format_unit_system(&content.system)?,
+                helpers::format_key_pairs(&content.attributes)?
+            ),
+            _ => {
+                return Err(Error::GeneratorError(format!(
+                    "Unsupported object kind in ADD command: {:?}",
+                    command.object_kind
+                )))
+            }
+        };
+        Ok(format!("ADD {}", instruction))
+    }
 }
 
 #[cfg(test)]
@@ -150,10 +272,10 @@ mod tests {
                             assert_eq!(instruction.kind, CommandType::Add);
                             assert_eq!(instruction.object_kind, ObjectKind::CaptureBase);
                             match instruction.content {
-                                Some(content) => {
-                                    assert!(content.attributes.is_some());
-                                    assert!(content.attributes.unwrap().len() > 0);
+                                Some(Content::CaptureBase(content)) => {
+                                    assert!(!content.attributes.is_empty());
                                 }
+                                Some(_) => panic!("Unexpected content"),
                                 None => {
                                     assert!(!is_valid, "Instruction is not valid");
                                 }
@@ -171,4 +293,47 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_add_unicode_and_quoted_keys() {
+        let instructions = vec![
+            (r#"ADD ATTRIBUTE "date of birth"=DateTime"#, "date of birth"),
+            ("ADD ATTRIBUTE data.urodzenia=DateTime", "data.urodzenia"),
+            ("ADD ATTRIBUTE имя=Text", "имя"),
+            ("ADD ATTRIBUTE 名前=Text", "名前"),
+            (r#"ADD ATTRIBUTE "say \"hi\""=Text"#, "say \"hi\""),
+            // decomposed "é" is normalised to its precomposed form
+            ("ADD ATTRIBUTE cafe\u{301}=Text", "caf\u{e9}"),
+        ];
+
+        for (instruction, expected_key) in instructions {
+            let parsed_instruction = OCAfileParser::parse(Rule::add, instruction)
+                .unwrap()
+                .next()
+                .unwrap();
+            let instruction = AddInstruction::from_record(parsed_instruction, 0).unwrap();
+            match instruction.content {
+                Some(Content::CaptureBase(content)) => {
+                    assert_eq!(content.attributes.keys().collect::<Vec<_>>(), vec![expected_key]);
+                }
+                _ => panic!("Expected capture base content"),
+            }
+        }
+
+        let parsed_instruction =
+            OCAfileParser::parse(Rule::add, r#"ADD LABEL pl ATTRS "data urodzenia"="Data urodzenia" imię=Imię"#)
+                .unwrap()
+                .next()
+                .unwrap();
+        let instruction = AddInstruction::from_record(parsed_instruction, 0).unwrap();
+        match instruction.content {
+            Some(Content::Label(content)) => {
+                assert_eq!(
+                    content.attributes.keys().collect::<Vec<_>>(),
+                    vec!["data urodzenia", "imię"]
+                );
+            }
+            _ => panic!("Expected label content"),
+        }
+    }
 }
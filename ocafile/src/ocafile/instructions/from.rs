@@ -1,7 +1,6 @@
 use crate::ocafile::{error::Error, Pair, Rule};
-use indexmap::IndexMap;
 use log::debug;
-use ocaast::ast::{Command, CommandType, Content, NestedValue, ObjectKind};
+use ocaast::ast::{BundleContent, Command, CommandType, Content, ObjectKind};
 use said::prefix::SelfAddressingPrefix;
 use std::str::FromStr;
 
@@ -25,19 +24,30 @@ impl FromInstruction {
             };
         }
 
-        let said = SelfAddressingPrefix::from_str(said_str.unwrap().as_str()).unwrap();
+        let said_str = said_str
+            .ok_or_else(|| Error::UnexpectedToken("FROM without SAID".to_string()))?
+            .as_str();
+        let said = SelfAddressingPrefix::from_str(said_str)
+            .map_err(|_| Error::ParserError(format!("invalid SAID in FROM: {}", said_str)))?;
         debug!("Using oca bundle from: {:?}", said);
-        let mut properties: IndexMap<String, NestedValue> = IndexMap::new();
-        properties.insert("said".to_string(), NestedValue::Value(said.to_string()));
         Ok(Command {
             kind: CommandType::From,
             object_kind: ObjectKind::OCABundle,
-            content: Some(Content {
-                properties: Some(properties),
-                attributes: None,
-            }),
+            content: Some(Content::Bundle(BundleContent {
+                said: said.to_string(),
+            })),
         })
     }
+
+    /// Generate FROM instruction from command
+    pub(crate) fn generate(command: &Command) -> Result<String, Error> {
+        match &command.content {
+            Some(Content::Bundle(content)) => Ok(format!("FROM {}", content.said)),
+            _ => Err(Error::GeneratorError(
+                "FROM command without SAID".to_string(),
+            )),
+        }
+    }
 }
 
 #[cfg(test)]
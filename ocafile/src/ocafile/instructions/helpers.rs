@@ -2,9 +2,100 @@ use std::str::FromStr;
 
 use indexmap::IndexMap;
 use log::debug;
-use oca_rs::state::attribute::AttributeType;
-use ocaast::ast::{Content, NestedValue};
-use crate::ocafile::{Pair, Rule};
+use ocaast::ast::AttributeType;
+use unicode_normalization::UnicodeNormalization;
+use crate::ocafile::{error::Error, Pair, Rule};
+
+/// Extract attribute or property key, unquoting it if needed
+///
+/// Keys are normalised to Unicode NFC so that the same name typed with
+/// precomposed or decomposed characters refers to the same attribute.
+pub fn extract_key(key_pair: Pair) -> String {
+    let key = match key_pair.clone().into_inner().next() {
+        Some(quoted) if quoted.as_rule() == Rule::string => unescape(quoted.as_str()),
+        _ => key_pair.as_str().to_string(),
+    };
+    key.nfc().collect()
+}
+
+/// Extract value, unquoting it if it was given as a string literal
+pub fn extract_value(value_pair: Pair) -> String {
+    match value_pair.clone().into_inner().next() {
+        Some(quoted) if quoted.as_rule() == Rule::string => unescape(quoted.as_str()),
+        _ => value_pair.as_str().to_string(),
+    }
+}
+
+/// Strip surrounding quotes from a string literal and resolve its escape
+/// sequences
+pub fn unescape(quoted: &str) -> String {
+    let inner = &quoted[1..quoted.len() - 1];
+    let mut unescaped = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('b') => unescaped.push('\u{0008}'),
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('f') => unescaped.push('\u{000C}'),
+            Some('r') => unescaped.push('\r'),
+            Some('u') => push_code_point(&mut unescaped, &mut chars, 4),
+            Some('U') => push_code_point(&mut unescaped, &mut chars, 8),
+            // escaped newline continues the string on the next line
+            Some('\n') => {}
+            Some('\r') => {
+                if chars.clone().next() == Some('\n') {
+                    chars.next();
+                }
+            }
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+fn push_code_point(unescaped: &mut String, chars: &mut std::str::Chars, len: usize) {
+    let hex: String = chars.take(len).collect();
+    match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+        Some(c) => unescaped.push(c),
+        None => debug!("Invalid unicode escape: {:?}", hex),
+    }
+}
+
+/// Extract attribute name and type for ADD ATTRIBUTE command
+pub fn extract_attribute_type_pair(attr_pair: Pair) -> Result<(String, AttributeType), Error> {
+    let mut key = String::new();
+    let mut attr_type = None;
+
+    debug!("Extract the attribute type: {:?}", attr_pair);
+    for item in attr_pair.into_inner() {
+        match item.as_rule() {
+            Rule::attr_key => {
+                key = extract_key(item);
+            }
+            Rule::attr_type => {
+                attr_type = Some(AttributeType::from_str(item.as_str()).map_err(|e| {
+                    Error::ParserError(format!("Invalid attribute type {:?}: {}", item.as_str(), e))
+                })?);
+            }
+            _ => {
+                return Err(Error::UnexpectedToken(format!(
+                    "Invalid attribute in {:?}",
+                    item.as_rule()
+                )))
+            }
+        }
+    }
+    let attr_type = attr_type
+        .ok_or_else(|| Error::ParserError(format!("Missing type of attribute {:?}", key)))?;
+    debug!("Attribute type: {:?}", attr_type);
+    Ok((key, attr_type))
+}
 
 /// Extract attributes key pairs for ADD and MODIFY command
 
@@ -16,19 +107,10 @@ pub fn extract_attribute_key_pairs(attr_pair: Pair) -> Option<(String, String)>
     for item in attr_pair.into_inner() {
         match item.as_rule() {
             Rule::attr_key => {
-                key = item.as_str().to_string();
+                key = extract_key(item);
             }
-            Rule::attr_type => match AttributeType::from_str(&item.as_span().as_str()) {
-                Ok(attr_type) => {
-                    debug!("Attribute type: {:?}", attr_type);
-                    value = attr_type.to_string();
-                }
-                Err(e) => {
-                    panic!("Invalid attribute type {:?}", e);
-                }
-            },
             Rule::key_value => {
-                value = item.as_str().to_string();
+                value = extract_value(item);
             }
             _ => {
                 panic!("Invalid attribute in {:?}", item.as_rule());
@@ -38,11 +120,20 @@ pub fn extract_attribute_key_pairs(attr_pair: Pair) -> Option<(String, String)>
     Some((key, value))
 }
 
+/// Arguments and key pairs of instruction, before they are shaped into
+/// content of specific object
+#[derive(Debug, Default)]
+pub struct ExtractedContent {
+    pub lang: Option<String>,
+    pub unit_system: Option<String>,
+    pub attributes: IndexMap<String, String>,
+    pub properties: IndexMap<String, String>,
+}
+
 // Extract content from instruction for ADD and MODIFY command
 
-pub fn extract_content(object: Pair) -> Option<Content> {
-    let mut properties: IndexMap<String, NestedValue> = IndexMap::new();
-    let mut attributes: IndexMap<String, NestedValue> = IndexMap::new();
+pub fn extract_content(object: Pair) -> Option<ExtractedContent> {
+    let mut content = ExtractedContent::default();
 
     debug!("Into the object: {:?}", object);
     for attr in object.into_inner() {
@@ -54,7 +145,7 @@ pub fn extract_content(object: Pair) -> Option<Content> {
                     if let Some((key, value)) = extract_attribute_key_pairs(attr) {
                         debug!("Parsed attribute: {:?} = {:?}", key, value);
                         // TODO find out how to parse nested objects
-                        attributes.insert(key, NestedValue::Value(value));
+                        content.attributes.insert(key, value);
                     } else {
                         debug!("Skipping attribute");
                     }
@@ -66,7 +157,7 @@ pub fn extract_content(object: Pair) -> Option<Content> {
                     if let Some((key, value)) = extract_attribute_key_pairs(prop) {
                         debug!("Parsed property: {:?} = {:?}", key, value);
                         // TODO find out how to parse nested objects
-                        properties.insert(key, NestedValue::Value(value));
+                        content.properties.insert(key, value);
                     } else {
                         debug!("Skipping property");
                     }
@@ -74,17 +165,11 @@ pub fn extract_content(object: Pair) -> Option<Content> {
             }
             Rule::lang => {
                 debug!("Parsing language: {:?}", attr.as_str());
-                properties.insert(
-                    "lang".to_string(),
-                    NestedValue::Value(attr.as_str().to_string()),
-                );
+                content.lang = Some(attr.as_str().to_string());
             }
             Rule::unit_system => {
                 debug!("Parsing unit system: {:?}", attr.as_str());
-                properties.insert(
-                    "unit_system".to_string(),
-                    NestedValue::Value(attr.as_str().to_string()),
-                );
+                content.unit_system = Some(attr.as_str().to_string());
             }
             _ => {
                 debug!(
@@ -96,8 +181,119 @@ pub fn extract_content(object: Pair) -> Option<Content> {
         }
     }
 
-    Some(Content {
-        properties: Some(properties),
-        attributes: Some(attributes),
-    })
-}
\ No newline at end of file
+    Some(content)
+}
+
+/// Format key for OCAfile, quoting it unless it is a plain ASCII identifier
+pub fn format_key(key: &str) -> String {
+    let is_bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if is_bare {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+/// Format value for OCAfile, quoting it unless it only contains characters
+/// allowed in unquoted values
+pub fn format_value(value: &str) -> String {
+    let is_bare = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'));
+    if is_bare {
+        value.to_string()
+    } else {
+        quote(value)
+    }
+}
+
+/// Wrap string in double quotes, escaping characters which can't appear
+/// literally inside of it
+pub fn quote(unquoted: &str) -> String {
+    let mut quoted = String::with_capacity(unquoted.len() + 2);
+    quoted.push('"');
+    for c in unquoted.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Format key pairs of ADD command content as `key=value` list, failing
+/// when there are none as grammar requires at least one pair
+pub fn format_key_pairs(pairs: &IndexMap<String, String>) -> Result<String, Error> {
+    if pairs.is_empty() {
+        return Err(Error::GeneratorError(
+            "Missing attributes or properties to add".to_string(),
+        ));
+    }
+    Ok(pairs
+        .iter()
+        .map(|(key, value)| format!("{}={}", format_key(key), format_value(value)))
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// Format language of command content, failing for languages grammar can't
+/// express, i.e. other than two letter code with optional two letter region
+pub fn format_lang(lang: &str) -> Result<&str, Error> {
+    let is_alpha_pair =
+        |part: &str| part.len() == 2 && part.chars().all(|c| c.is_ascii_alphabetic());
+    let is_valid = match lang.split_once('-') {
+        Some((language, region)) => is_alpha_pair(language) && is_alpha_pair(region),
+        None => is_alpha_pair(lang),
+    };
+    if is_valid {
+        Ok(lang)
+    } else {
+        Err(Error::GeneratorError(format!("Invalid language {:?}", lang)))
+    }
+}
+
+/// Format metric system of unit content, failing for systems grammar can't
+/// express
+pub fn format_unit_system(system: &str) -> Result<&str, Error> {
+    let is_valid = !system.is_empty()
+        && system
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+    if is_valid {
+        Ok(system)
+    } else {
+        Err(Error::GeneratorError(format!("Invalid unit system {:?}", system)))
+    }
+}
+
+/// Format attributes of capture base as `key=Type` list
+pub fn format_attribute_types(
+    attributes: &IndexMap<String, Option<AttributeType>>,
+) -> Result<String, Error> {
+    attributes
+        .iter()
+        .map(|(key, attr_type)| match attr_type {
+            Some(attr_type) => Ok(format!("{}={}", format_key(key), attr_type)),
+            None => Err(Error::GeneratorError(format!(
+                "Missing type of attribute {:?}",
+                key
+            ))),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(|pairs| pairs.join(" "))
+}
+
+/// Format keys of REMOVE command content as space separated list
+pub fn format_keys<'a>(keys: impl Iterator<Item = &'a String>) -> String {
+    keys.map(|key| format_key(key)).collect::<Vec<_>>().join(" ")
+}
@@ -1,7 +1,10 @@
-use crate::ocafile::{error::Error, Pair, Rule};
+use crate::ocafile::{error::Error, instructions::helpers, Pair, Rule};
 use indexmap::IndexMap;
 use log::debug;
-use ocaast::ast::{Command, CommandType, Content, NestedValue, ObjectKind, OverlayType};
+use ocaast::ast::{
+    AttributeType, CaptureBaseContent, Command, CommandType, Content, LabelContent, MetaContent,
+    ObjectKind, OverlayType,
+};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct RemoveInstruction {}
@@ -16,36 +19,40 @@ impl RemoveInstruction {
             content = match object.as_rule() {
                 Rule::remove_meta => {
                     object_kind = Some(ObjectKind::Overlay(OverlayType::Meta));
-                    extract_content(object)
+                    extract_content(object).map(|content| {
+                        Content::Meta(MetaContent {
+                            lang: content.lang.unwrap_or_default(),
+                            properties: content.properties,
+                        })
+                    })
                 }
-                Rule::classification => {
+                Rule::classification | Rule::remove_classification => {
                     object_kind = Some(ObjectKind::CaptureBase);
-                    let mut properties: IndexMap<String, NestedValue> = IndexMap::new();
-                    properties.insert(
-                        "classification".to_string(),
-                        NestedValue::Value("".to_string()),
-                    );
-                    Some(Content {
-                        properties: Some(properties),
-                        attributes: None,
-                    })
+                    Some(Content::CaptureBase(CaptureBaseContent {
+                        classification: Some("".to_string()),
+                        ..Default::default()
+                    }))
                 }
                 Rule::remove_label => {
                     object_kind = Some(ObjectKind::Overlay(OverlayType::Label));
-                    extract_content(object)
+                    extract_content(object).map(|content| {
+                        Content::Label(LabelContent {
+                            lang: content.lang.unwrap_or_default(),
+                            attributes: content.attributes,
+                        })
+                    })
                 }
                 Rule::remove_attribute => {
                     object_kind = Some(ObjectKind::CaptureBase);
-                    let mut attributes: IndexMap<String, NestedValue> = IndexMap::new();
+                    let mut attributes: IndexMap<String, Option<AttributeType>> = IndexMap::new();
                     for key in object.into_inner() {
                         debug!("Parsing key to remove: {:?}", key.as_str());
-                        attributes
-                            .insert(key.as_str().to_string(), NestedValue::Value("".to_string()));
+                        attributes.insert(helpers::extract_key(key), None);
                     }
-                    Some(Content {
-                        properties: None,
-                        attributes: Some(attributes),
-                    })
+                    Some(Content::CaptureBase(CaptureBaseContent {
+                        attributes,
+                        ..Default::default()
+                    }))
                 }
                 _ => {
                     return Err(Error::UnexpectedToken(format!(
@@ -62,14 +69,53 @@ impl RemoveInstruction {
             content: content,
         })
     }
+
+    /// Generate REMOVE instruction from command
+    pub(crate) fn generate(command: &Command) -> Result<String, Error> {
+        let instruction = match &command.content {
+            Some(Content::CaptureBase(content)) => {
+                if !content.attributes.is_empty() {
+                    format!("ATTRIBUTE {}", helpers::format_keys(content.attributes.keys()))
+                } else if content.classification.is_some() {
+                    "CLASSIFICATION".to_string()
+                } else {
+                    return Err(Error::GeneratorError(
+                        "Empty capture base command".to_string(),
+                    ));
+                }
+            }
+            Some(Content::Meta(content)) if content.properties.is_empty() => {
+                format!("META {}", helpers::format_lang(&content.lang)?)
+            }
+            Some(Content::Meta(content)) => format!(
+                "META {} PROPS {}",
+                helpers::format_lang(&content.lang)?,
+                helpers::format_keys(content.properties.keys())
+            ),
+            Some(Content::Label(content)) if content.attributes.is_empty() => {
+                format!("LABEL {}", helpers::format_lang(&content.lang)?)
+            }
+            Some(Content::Label(content)) => format!(
+                "LABEL {} ATTRS {}",
+                helpers::format_lang(&content.lang)?,
+                helpers::format_keys(content.attributes.keys())
+            ),
+            _ => {
+                return Err(Error::GeneratorError(format!(
+                    "Unsupported object kind in REMOVE command: {:?}",
+                    command.object_kind
+                )))
+            }
+        };
+        Ok(format!("REMOVE {}", instruction))
+    }
 }
 
 
-// Extract content from instruction for ADD and MODIFY command
+// Extract keys to remove from instruction for REMOVE command
 
-fn extract_content(object: Pair) -> Option<Content> {
-    let mut properties: IndexMap<String, NestedValue> = IndexMap::new();
-    let mut attributes: IndexMap<String, NestedValue> = IndexMap::new();
+fn extract_content(object: Pair) -> Option<helpers::ExtractedContent> {
+    let mut content = helpers::ExtractedContent::default();
 
     debug!("Into the object: {:?}", object);
     for attr in object.into_inner() {
@@ -77,26 +123,19 @@ fn extract_content(object: Pair) -> Option<Content> {
         match attr.as_rule() {
             Rule::attr_key => {
                 debug!("Parsed attribute: {:?}", attr);
-                // TODO find out how to parse nested objects
-                attributes.insert(
-                    attr.as_str().to_string(),
-                    NestedValue::Value("".to_string()),
-                );
+                content
+                    .attributes
+                    .insert(helpers::extract_key(attr), "".to_string());
             }
             Rule::prop_key => {
                 debug!("Parsed attribute: {:?}", attr);
-                // TODO find out how to parse nested objects
-                properties.insert(
-                    attr.as_str().to_string(),
-                    NestedValue::Value("".to_string()),
-                );
+                content
+                    .properties
+                    .insert(helpers::extract_key(attr), "".to_string());
             }
             Rule::lang => {
                 debug!("Parsing language: {:?}", attr.as_str());
-                properties.insert(
-                    "lang".to_string(),
-                    NestedValue::Value(attr.as_str().to_string()),
-                );
+                content.lang = Some(attr.as_str().to_string());
             }
             _ => {
                 debug!(
@@ -107,10 +146,7 @@ fn extract_content(object: Pair) -> Option<Content> {
             }
         }
     }
-    debug!("Parsed properties: {:?}", properties);
-    debug!("Parsed attributes: {:?}", attributes);
-    Some(Content {
-        properties: Some(properties),
-        attributes: Some(attributes),
-    })
+    debug!("Parsed properties: {:?}", content.properties);
+    debug!("Parsed attributes: {:?}", content.attributes);
+    Some(content)
 }
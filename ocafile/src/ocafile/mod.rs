@@ -1,8 +1,9 @@
 mod instructions;
-mod error;
+pub mod error;
 
 use self::instructions::{from::FromInstruction, add::AddInstruction, remove::RemoveInstruction};
 use ocaast::ast::{OCAAst, Command, CommandType};
+use ocaast::report::Span;
 use crate::ocafile::error::Error;
 use core::convert::From;
 use pest::Parser;
@@ -54,12 +55,31 @@ impl TryFromPair for Command {
 
  /// Parse OCAfile from string and generate OCABox
  pub fn parse_from_string(unparsed_file: String) -> OCAAst {
+    parse_with_spans(unparsed_file).0
+}
+
+/// Parse OCAfile from string, together with location of every command in it
+///
+/// Spans are indexed the same way as commands of returned AST, so they can be
+/// attached to validation report.
+pub fn parse_with_spans(unparsed_file: String) -> (OCAAst, Vec<Span>) {
+    match try_parse_with_spans(unparsed_file) {
+        Ok(parsed) => parsed,
+        Err(Error::ParserError(e)) => panic!("unsuccessful parse: {}", e),
+        Err(e) => panic!("Error parsing instruction: {}", e),
+    }
+}
+
+/// Like `parse_with_spans`, but errors in OCAfile are returned, not panicked
+/// on
+pub fn try_parse_with_spans(unparsed_file: String) -> Result<(OCAAst, Vec<Span>), Error> {
     let file = OCAfileParser::parse(Rule::file, &unparsed_file)
-        .expect("unsuccessful parse")
+        .map_err(|e| Error::ParserError(e.to_string()))?
         .next()
         .unwrap();
 
     let mut oca_ast = OCAAst::new();
+    let mut spans = Vec::new();
 
 
     for line in file.into_inner() {
@@ -73,14 +93,227 @@ impl TryFromPair for Command {
             continue;
         }
 
-        let _command = match Command::try_from_pair(line) {
-            Ok(command) => {
-                oca_ast.commands.push(command);
-            },
-            Err(e) => {
-                panic!("Error parsing instruction: {}", e);
+        let span = line.as_span();
+        let (line_number, column) = span.start_pos().line_col();
+        oca_ast.commands.push(Command::try_from_pair(line)?);
+        spans.push(Span {
+            start: span.start(),
+            end: span.end(),
+            line: line_number,
+            column,
+        });
+    }
+    Ok((oca_ast, spans))
+}
+
+/// Generate OCAfile from OCA AST, one instruction per command
+///
+/// Commands no single instruction can express, e.g. capture base command
+/// with both attributes and classification, are rejected, so that parsing
+/// generated OCAfile gives the same AST back.
+pub fn generate_from_ast(ast: &OCAAst) -> Result<String, Error> {
+    let mut ocafile = String::new();
+    for command in &ast.commands {
+        let instruction = match command.kind {
+            CommandType::From => FromInstruction::generate(command)?,
+            CommandType::Add => AddInstruction::generate(command)?,
+            CommandType::Remove => RemoveInstruction::generate(command)?,
+            CommandType::Modify => {
+                return Err(Error::GeneratorError(
+                    "MODIFY command is not supported".to_string(),
+                ))
             }
         };
+        ocafile.push_str(&instruction);
+        ocafile.push('\n');
+    }
+    Ok(ocafile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexmap::IndexMap;
+    use ocaast::ast::{
+        AttributeType, CaptureBaseContent, CharacterEncodingContent, Content, LabelContent,
+        MetaContent, ObjectKind, OverlayType, UnitContent,
+    };
+
+    #[test]
+    fn test_parse_ast_fixture() {
+        // fixture of the ocaast round trip test is what the parser gives
+        // for the OCAfile next to it
+        let ast =
+            parse_from_string(include_str!("../../../ocaast/fixtures/example.ocafile").to_string());
+        let fixture: OCAAst =
+            serde_json::from_str(include_str!("../../../ocaast/fixtures/example.ocafile.ast"))
+                .unwrap();
+        assert_eq!(ast, fixture);
+    }
+
+    #[test]
+    fn test_generate_round_trip() {
+        let unparsed_file = r#"
+FROM E2oRZ5zEKxTfTdECW-v2Q7bM_H0OD0ko7IcCwdo_u9co
+ADD CLASSIFICATION "GICS:45102010"
+ADD ATTRIBUTE documentNumber=Text "date of birth"=DateTime list=Array[Numeric]
+ADD ATTRIBUTE imię=Text
+ADD FLAGGED_ATTRIBUTES documentNumber "date of birth"
+ADD META en PROPS name="Passport" description="The word for \"passport\"\tand a tab"
+ADD LABEL pl ATTRS imię="Imię" "date of birth"="Data urodzenia"
+ADD INFORMATION en ATTRS documentNumber="Unique identification number of the document."
+ADD CHARACTER_ENCODING ATTRS imię=utf-8
+ADD CHARACTER_ENCODING PROPS default_encoding=utf-8
+ADD FORMAT documentNumber="[A-Z0-9]{9}"
+ADD UNIT si ATTRS list=cm
+REMOVE ATTRIBUTE list
+REMOVE CLASSIFICATION
+REMOVE LABEL pl ATTRS "date of birth"
+REMOVE LABEL pl
+REMOVE META en PROPS description
+REMOVE META en
+"#;
+        let ast = parse_from_string(unparsed_file.to_string());
+        assert_eq!(ast.commands.len(), 18);
+
+        let generated = generate_from_ast(&ast).unwrap();
+        assert_eq!(generated.lines().count(), ast.commands.len());
+        assert_eq!(parse_from_string(generated), ast);
+    }
+
+    #[test]
+    fn test_generate_quotes_values() {
+        let mut attributes = IndexMap::new();
+        attributes.insert("documentNumber".to_string(), "Passport Number".to_string());
+        attributes.insert("full name".to_string(), "Full \"name\"".to_string());
+
+        let mut ast = OCAAst::new();
+        ast.commands.push(Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::Label),
+            content: Some(Content::Label(LabelContent {
+                lang: "en".to_string(),
+                attributes,
+            })),
+        });
+
+        let generated = generate_from_ast(&ast).unwrap();
+        assert_eq!(
+            generated,
+            "ADD LABEL en ATTRS documentNumber=\"Passport Number\" \"full name\"=\"Full \\\"name\\\"\"\n"
+        );
+        assert_eq!(parse_from_string(generated), ast);
+    }
+
+    #[test]
+    fn test_generate_rejects_commands_without_instruction() {
+        let capture_base = Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::CaptureBase,
+            content: Some(Content::CaptureBase(CaptureBaseContent {
+                attributes: IndexMap::from([("name".to_string(), Some(AttributeType::Text))]),
+                classification: Some("GICS:45102010".to_string()),
+                flagged_attributes: vec!["name".to_string()],
+            })),
+        };
+        let character_encoding = Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::CharacterEncoding),
+            content: Some(Content::CharacterEncoding(CharacterEncodingContent {
+                attributes: IndexMap::from([("name".to_string(), "utf-8".to_string())]),
+                default_encoding: Some("utf-8".to_string()),
+            })),
+        };
+        for command in [capture_base, character_encoding] {
+            let mut ast = OCAAst::new();
+            ast.commands.push(command);
+            assert!(matches!(
+                generate_from_ast(&ast),
+                Err(Error::GeneratorError(_))
+            ));
+        }
     }
-    return oca_ast;
-}
\ No newline at end of file
+
+    #[test]
+    fn test_generate_rejects_content_grammar_cannot_express() {
+        let label = |lang: &str, attributes: IndexMap<String, String>| Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::Label),
+            content: Some(Content::Label(LabelContent {
+                lang: lang.to_string(),
+                attributes,
+            })),
+        };
+        let unit = |system: &str| Command {
+            kind: CommandType::Add,
+            object_kind: ObjectKind::Overlay(OverlayType::Unit),
+            content: Some(Content::Unit(UnitContent {
+                system: system.to_string(),
+                attributes: IndexMap::from([("height".to_string(), "cm".to_string())]),
+            })),
+        };
+        let name = || IndexMap::from([("name".to_string(), "Name".to_string())]);
+        let commands = [
+            label("en", IndexMap::new()),
+            label("", name()),
+            label("eng", name()),
+            label("en GB", name()),
+            label("en-", name()),
+            Command {
+                kind: CommandType::Add,
+                object_kind: ObjectKind::Overlay(OverlayType::Meta),
+                content: Some(Content::Meta(MetaContent {
+                    lang: "en".to_string(),
+                    properties: IndexMap::new(),
+                })),
+            },
+            Command {
+                kind: CommandType::Add,
+                object_kind: ObjectKind::Overlay(OverlayType::CharacterEncoding),
+                content: Some(Content::CharacterEncoding(CharacterEncodingContent {
+                    attributes: IndexMap::new(),
+                    default_encoding: None,
+                })),
+            },
+            unit(""),
+            unit("si/x"),
+            unit("si x"),
+            Command {
+                kind: CommandType::Remove,
+                object_kind: ObjectKind::Overlay(OverlayType::Label),
+                content: Some(Content::Label(LabelContent {
+                    lang: "e1".to_string(),
+                    attributes: IndexMap::new(),
+                })),
+            },
+        ];
+        for command in commands {
+            let mut ast = OCAAst::new();
+            ast.commands.push(command);
+            assert!(matches!(
+                generate_from_ast(&ast),
+                Err(Error::GeneratorError(_))
+            ));
+        }
+
+        let mut ast = OCAAst::new();
+        ast.commands.push(label("en-GB", name()));
+        ast.commands.push(unit("metric_2-x"));
+        let generated = generate_from_ast(&ast).unwrap();
+        assert_eq!(parse_from_string(generated), ast);
+    }
+
+    #[test]
+    fn test_parse_with_spans() {
+        let unparsed_file = "# comment\nADD ATTRIBUTE name=Text\n\nADD LABEL en ATTRS name=Name\n";
+        let (ast, spans) = parse_with_spans(unparsed_file.to_string());
+        assert_eq!(ast.commands.len(), 2);
+        assert_eq!(spans.len(), 2);
+        assert_eq!((spans[0].line, spans[0].column), (2, 1));
+        assert_eq!((spans[1].line, spans[1].column), (4, 1));
+        assert_eq!(
+            &unparsed_file[spans[1].start..spans[1].end],
+            "ADD LABEL en ATTRS name=Name"
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! Remote OCA repository over HTTP
+//!
+//! Repository API, served by `ocaserver` and described in full in
+//! `openapi.yaml`, of which the client uses:
+//!
+//! * `PUT /bundles/<said>` with bundle JSON stores the bundle, answering
+//!   `201 Created` for a new bundle and `200 OK` if it was there already,
+//!   both with `{"said": "<said>"}` of the stored bundle. `ocaserver`
+//!   verifies SAIDs so it never holds other content under one, `409
+//!   Conflict` of repositories that don't is reported as a conflict.
+//! * `GET /bundles/<said>` gives the bundle JSON, `404 Not Found` if there
+//!   is no such bundle.
+//! * `GET /bundles/<said>/ast` gives AST the bundle was built from, `404 Not
+//!   Found` if it is not known.
+
+use serde::Deserialize;
+use std::io::Read;
+
+use ocaast::ast::OCAAst;
+
+use crate::{
+    bundle::Bundle,
+    ocafile::error::Error,
+    resolver::{check_resolved, BundleResolver},
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PublishOutcome {
+    Created,
+    /// Repository had the bundle already, nothing changed
+    AlreadyPublished,
+}
+
+#[derive(Deserialize)]
+struct Stored {
+    said: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoteRepository {
+    url: String,
+}
+
+impl RemoteRepository {
+    pub fn new(url: &str) -> Self {
+        RemoteRepository {
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn bundle_url(&self, said: &str) -> String {
+        format!("{}/bundles/{}", self.url, said)
+    }
+
+    /// Upload verified bundle
+    pub fn publish(&self, bundle: &Bundle) -> Result<PublishOutcome, Error> {
+        bundle.verify()?;
+        let response = ureq::put(&self.bundle_url(&bundle.said))
+            .set("Content-Type", "application/json")
+            .send_string(&bundle.to_json()?);
+        let (outcome, response) = match response {
+            Ok(response) if response.status() == 201 => (PublishOutcome::Created, response),
+            Ok(response) => (PublishOutcome::AlreadyPublished, response),
+            Err(ureq::Error::Status(409, response)) => {
+                return Err(Error::PublishConflict(
+                    bundle.said.clone(),
+                    response.into_string().unwrap_or_default(),
+                ))
+            }
+            Err(e) => return Err(remote_error(e)),
+        };
+        let stored: Stored = response
+            .into_json()
+            .map_err(|e| Error::RemoteError(format!("invalid response: {}", e)))?;
+        if stored.said != bundle.said {
+            return Err(Error::RemoteError(format!(
+                "repository stored {} in place of {}",
+                stored.said, bundle.said
+            )));
+        }
+        Ok(outcome)
+    }
+
+    /// Upload bundle after the bundles it refers to
+    ///
+    /// Referenced bundles are taken from `local`, those missing there must
+    /// be published already.
+    pub fn publish_with_references(
+        &self,
+        bundle: &Bundle,
+        local: &dyn BundleResolver,
+    ) -> Result<Vec<(String, PublishOutcome)>, Error> {
+        let mut published = Vec::new();
+        self.publish_tree(bundle, local, &mut published)?;
+        Ok(published)
+    }
+
+    fn publish_tree(
+        &self,
+        bundle: &Bundle,
+        local: &dyn BundleResolver,
+        published: &mut Vec<(String, PublishOutcome)>,
+    ) -> Result<(), Error> {
+        if published.iter().any(|(said, _)| *said == bundle.said) {
+            return Ok(());
+        }
+        for said in bundle.references() {
+            match local.resolve(&said) {
+                Ok(referenced) => self.publish_tree(&referenced, local, published)?,
+                Err(Error::BundleNotFound(_)) => {
+                    if !self.contains(&said)? {
+                        return Err(Error::RemoteError(format!(
+                            "referenced bundle {} is neither local nor published",
+                            said
+                        )));
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        let outcome = self.publish(bundle)?;
+        published.push((bundle.said.clone(), outcome));
+        Ok(())
+    }
+
+    pub fn contains(&self, said: &str) -> Result<bool, Error> {
+        match ureq::head(&self.bundle_url(said)).call() {
+            Ok(_) => Ok(true),
+            Err(ureq::Error::Status(404, _)) => Ok(false),
+            Err(e) => Err(remote_error(e)),
+        }
+    }
+}
+
+impl BundleResolver for RemoteRepository {
+    fn resolve(&self, said: &str) -> Result<Bundle, Error> {
+        let bundle = match ureq::get(&self.bundle_url(said)).call() {
+            Ok(response) => response
+                .into_json()
+                .map_err(|e| Error::RemoteError(format!("invalid bundle {}: {}", said, e)))?,
+            Err(ureq::Error::Status(404, _)) => {
+                return Err(Error::BundleNotFound(said.to_string()))
+            }
+            Err(e) => return Err(remote_error(e)),
+        };
+        check_resolved(said, bundle)
+    }
+
+    fn resolve_ast(&self, said: &str) -> Result<Option<OCAAst>, Error> {
+        match ureq::get(&format!("{}/ast", self.bundle_url(said))).call() {
+            Ok(response) => response
+                .into_json()
+                .map(Some)
+                .map_err(|e| Error::RemoteError(format!("invalid AST of {}: {}", said, e))),
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(remote_error(e)),
+        }
+    }
+}
+
+fn remote_error(e: ureq::Error) -> Error {
+    match e {
+        ureq::Error::Status(status, response) => {
+            let mut message = String::new();
+            let _ = response
+                .into_reader()
+                .take(1024)
+                .read_to_string(&mut message);
+            Error::RemoteError(format!("repository answered {}: {}", status, message))
+        }
+        e => Error::RemoteError(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{bundle::tests::build, ocafile::parse_from_string, resolver::InMemoryResolver};
+    use indexmap::IndexMap;
+    use ocaast::{
+        ast::{Content, NestedValue, OverlayContent, OverlayType},
+        state::{OCAState, OverlayKey},
+    };
+    use std::{
+        collections::HashMap,
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    /// Stand-in repository, serving `/bundles/<said>` from memory
+    pub(crate) fn stand_in_server() -> (String, Arc<Mutex<HashMap<String, String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let bundles = Arc::new(Mutex::new(HashMap::<String, String>::new()));
+        let store = bundles.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let body = String::from_utf8(body).unwrap();
+
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap_or_default();
+                let said = parts
+                    .next()
+                    .unwrap_or_default()
+                    .trim_start_matches("/bundles/")
+                    .to_string();
+                let mut bundles = store.lock().unwrap();
+                let stored = format!(r#"{{"said":"{}"}}"#, said);
+                let (status, body) = match (method, bundles.get(&said)) {
+                    ("PUT", Some(existing)) if *existing == body => ("200 OK", stored),
+                    ("PUT", Some(_)) => ("409 Conflict", "other bundle stored".to_string()),
+                    ("PUT", None) => {
+                        bundles.insert(said, body);
+                        ("201 Created", stored)
+                    }
+                    ("GET" | "HEAD", Some(existing)) => ("200 OK", existing.clone()),
+                    _ => ("404 Not Found", String::new()),
+                };
+                let body = if method == "HEAD" {
+                    String::new()
+                } else {
+                    body
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        (url, bundles)
+    }
+
+    pub(crate) fn code_table() -> Bundle {
+        build("ADD ATTRIBUTE code=Text\nADD LABEL en ATTRS code=Code\n")
+    }
+
+    /// Bundle with entry codes taken from the code table
+    pub(crate) fn bundle_with_code_table(code_table: &Bundle) -> Bundle {
+        let mut state = OCAState::from_ast(&parse_from_string(
+            "ADD ATTRIBUTE country=Text\n".to_string(),
+        ));
+        let content = Content::Overlay(OverlayContent {
+            attributes: IndexMap::from([(
+                "country".to_string(),
+                NestedValue::Reference(code_table.said.clone()),
+            )]),
+            properties: IndexMap::new(),
+        });
+        state
+            .overlays
+            .insert(OverlayKey::new(&OverlayType::EntryCode, &content), content);
+        Bundle::from_state(&state).unwrap()
+    }
+
+    #[test]
+    fn test_publish() {
+        let (url, stored) = stand_in_server();
+        let remote = RemoteRepository::new(&url);
+        let code_table = code_table();
+        let bundle = bundle_with_code_table(&code_table);
+        assert_eq!(bundle.references(), vec![code_table.said.clone()]);
+
+        // code table is neither local nor published
+        assert!(remote
+            .publish_with_references(&bundle, &InMemoryResolver::new())
+            .is_err());
+
+        let mut local = InMemoryResolver::new();
+        local.insert(code_table.clone());
+        assert_eq!(
+            remote.publish_with_references(&bundle, &local).unwrap(),
+            vec![
+                (code_table.said.clone(), PublishOutcome::Created),
+                (bundle.said.clone(), PublishOutcome::Created),
+            ]
+        );
+        assert_eq!(
+            remote.publish(&bundle).unwrap(),
+            PublishOutcome::AlreadyPublished
+        );
+        assert!(remote.contains(&code_table.said).unwrap());
+        assert!(!remote
+            .contains("EAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA")
+            .unwrap());
+
+        stored
+            .lock()
+            .unwrap()
+            .insert(bundle.said.clone(), "{}".to_string());
+        assert!(matches!(
+            remote.publish(&bundle),
+            Err(Error::PublishConflict(said, _)) if said == bundle.said
+        ));
+
+        let mut tampered = code_table;
+        tampered
+            .capture_base
+            .flagged_attributes
+            .push("code".to_string());
+        assert!(remote.publish(&tampered).is_err());
+    }
+}
@@ -0,0 +1,456 @@
+//! Local repository of OCA bundles
+//!
+//! Bundles are kept in sled, split into their parts: capture bases and
+//! overlays are stored once by their SAIDs, shared by all bundles which
+//! use them, and every bundle is stored as a record of SAIDs of its parts.
+//! Stored bundles are indexed for search, see `search` module. ASTs and
+//! OCAfiles of bundles, when known, are kept next to them.
+
+use ocaast::ast::OCAAst;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, path::Path};
+
+use crate::{
+    bundle::{to_json, Bundle, CaptureBase, Overlay},
+    digest::said_digest,
+    ocafile::error::Error,
+    resolver::{from_said, BundleResolver, ChainResolver},
+    search::{index_keys, key_said, SearchQuery},
+};
+
+const BUNDLES_TREE: &str = "bundles";
+const OBJECTS_TREE: &str = "objects";
+const INDEX_TREE: &str = "index";
+const ASTS_TREE: &str = "asts";
+const OCAFILES_TREE: &str = "ocafiles";
+
+/// Bundle as stored, with SAIDs in place of its parts
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct BundleRecord {
+    capture_base: String,
+    overlays: Vec<String>,
+}
+
+pub struct Repository {
+    bundles: sled::Tree,
+    objects: sled::Tree,
+    index: sled::Tree,
+    asts: sled::Tree,
+    ocafiles: sled::Tree,
+}
+
+impl Repository {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_db(sled::open(path).map_err(repository_error)?)
+    }
+
+    /// Repository removed when dropped, for tests
+    pub fn temporary() -> Result<Self, Error> {
+        Self::from_db(
+            sled::Config::new()
+                .temporary(true)
+                .open()
+                .map_err(repository_error)?,
+        )
+    }
+
+    fn from_db(db: sled::Db) -> Result<Self, Error> {
+        Ok(Repository {
+            bundles: db.open_tree(BUNDLES_TREE).map_err(repository_error)?,
+            objects: db.open_tree(OBJECTS_TREE).map_err(repository_error)?,
+            index: db.open_tree(INDEX_TREE).map_err(repository_error)?,
+            asts: db.open_tree(ASTS_TREE).map_err(repository_error)?,
+            ocafiles: db.open_tree(OCAFILES_TREE).map_err(repository_error)?,
+        })
+    }
+
+    /// Store verified bundle, parts already in the repository are kept
+    pub fn store(&self, bundle: &Bundle) -> Result<(), Error> {
+        bundle.verify()?;
+        self.store_object(&bundle.capture_base.said, &bundle.capture_base)?;
+        for overlay in &bundle.overlays {
+            self.store_object(&overlay.said, overlay)?;
+        }
+        let record = BundleRecord {
+            capture_base: bundle.capture_base.said.clone(),
+            overlays: bundle.overlays.iter().map(|o| o.said.clone()).collect(),
+        };
+        self.bundles
+            .insert(&bundle.said, to_json(&record)?.as_bytes())
+            .map_err(repository_error)?;
+        for key in index_keys(bundle) {
+            self.index.insert(key, &[]).map_err(repository_error)?;
+        }
+        self.bundles.flush().map_err(repository_error)?;
+        Ok(())
+    }
+
+    fn store_object<T: Serialize>(&self, said: &str, object: &T) -> Result<(), Error> {
+        if !self.objects.contains_key(said).map_err(repository_error)? {
+            self.objects
+                .insert(said, to_json(object)?.as_bytes())
+                .map_err(repository_error)?;
+        }
+        Ok(())
+    }
+
+    /// Store AST the bundle of given SAID was built from
+    pub fn store_ast(&self, said: &str, ast: &OCAAst) -> Result<(), Error> {
+        let serialized =
+            serde_json::to_vec(ast).map_err(|e| Error::RepositoryError(e.to_string()))?;
+        self.asts
+            .insert(said, serialized)
+            .map_err(repository_error)?;
+        Ok(())
+    }
+
+    pub fn get_ast(&self, said: &str) -> Result<Option<OCAAst>, Error> {
+        match self.asts.get(said).map_err(repository_error)? {
+            Some(ast) => decode(said, &ast).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Store OCAfile the bundle of given SAID was built from
+    pub fn store_ocafile(&self, said: &str, ocafile: &str) -> Result<(), Error> {
+        self.ocafiles
+            .insert(said, ocafile.as_bytes())
+            .map_err(repository_error)?;
+        Ok(())
+    }
+
+    pub fn get_ocafile(&self, said: &str) -> Result<Option<String>, Error> {
+        Ok(self
+            .ocafiles
+            .get(said)
+            .map_err(repository_error)?
+            .map(|ocafile| String::from_utf8_lossy(&ocafile).into_owned()))
+    }
+
+    /// Fetch bundle from source and store it, together with its AST if the
+    /// source knows it
+    ///
+    /// AST is stored only if building it on its FROM base, with the digest of
+    /// the bundle SAID, gives the fetched bundle; otherwise it is dropped.
+    ///
+    /// With `transitive` also the bundles it refers to are fetched: its FROM
+    /// base, known from AST, and bundles referred to by overlays. Bundles
+    /// already in the repository are not fetched again. Returns SAIDs of
+    /// fetched bundles. Reference attributes of capture bases are not
+    /// followed, as AST and bundle don't say which bundle they refer to.
+    pub fn fetch(
+        &self,
+        said: &str,
+        source: &dyn BundleResolver,
+        transitive: bool,
+    ) -> Result<Vec<String>, Error> {
+        let mut pending = vec![said.to_string()];
+        let mut visited = Vec::new();
+        let mut fetched = Vec::new();
+        while let Some(said) = pending.pop() {
+            if visited.contains(&said) {
+                continue;
+            }
+            visited.push(said.clone());
+            let (bundle, ast) = match self.get(&said)? {
+                Some(bundle) => (bundle, self.get_ast(&said)?),
+                None => {
+                    let bundle = source.resolve(&said)?;
+                    self.store(&bundle)?;
+                    let ast = match source.resolve_ast(&said)? {
+                        Some(ast) if self.builds(&ast, &said, source)? => Some(ast),
+                        _ => None,
+                    };
+                    if let Some(ast) = &ast {
+                        self.store_ast(&said, ast)?;
+                    }
+                    fetched.push(said);
+                    (bundle, ast)
+                }
+            };
+            if transitive {
+                pending.extend(bundle.references());
+                if let Some(from) = ast.as_ref().and_then(from_said) {
+                    pending.push(from.to_string());
+                }
+            }
+        }
+        Ok(fetched)
+    }
+
+    /// Whether AST builds bundle with given SAID, FROM base resolved here or
+    /// from source
+    fn builds(&self, ast: &OCAAst, said: &str, source: &dyn BundleResolver) -> Result<bool, Error> {
+        let resolver = ChainResolver::new().with(self).with(source);
+        Ok(
+            Bundle::from_ast_resolved_with_digest(ast, &resolver, &said_digest(said)?)
+                .is_ok_and(|bundle| bundle.said == said),
+        )
+    }
+
+    pub fn contains(&self, said: &str) -> Result<bool, Error> {
+        self.bundles.contains_key(said).map_err(repository_error)
+    }
+
+    pub fn get(&self, said: &str) -> Result<Option<Bundle>, Error> {
+        let record: BundleRecord = match self.bundles.get(said).map_err(repository_error)? {
+            Some(record) => decode(said, &record)?,
+            None => return Ok(None),
+        };
+        let capture_base: CaptureBase = self.get_object(&record.capture_base)?;
+        let overlays = record
+            .overlays
+            .iter()
+            .map(|said| self.get_object::<Overlay>(said))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(Bundle {
+            said: said.to_string(),
+            capture_base,
+            overlays,
+        }))
+    }
+
+    fn get_object<T: for<'de> Deserialize<'de>>(&self, said: &str) -> Result<T, Error> {
+        match self.objects.get(said).map_err(repository_error)? {
+            Some(object) => decode(said, &object),
+            None => Err(Error::RepositoryError(format!("missing object {}", said))),
+        }
+    }
+
+    /// SAIDs of all stored bundles
+    pub fn list(&self) -> Result<Vec<String>, Error> {
+        self.bundles
+            .iter()
+            .keys()
+            .map(|said| Ok(String::from_utf8_lossy(&said.map_err(repository_error)?).into_owned()))
+            .collect()
+    }
+
+    /// SAIDs of bundles matching all terms of query, in order of SAIDs
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<String>, Error> {
+        let mut found: Option<BTreeSet<String>> = None;
+        for term in query.terms() {
+            let matching = self
+                .index
+                .scan_prefix(term.prefix())
+                .keys()
+                .map(|key| Ok(key_said(&key.map_err(repository_error)?)))
+                .collect::<Result<BTreeSet<_>, Error>>()?;
+            found = Some(match found {
+                Some(found) => found.intersection(&matching).cloned().collect(),
+                None => matching,
+            });
+        }
+        match found {
+            Some(found) => Ok(found.into_iter().collect()),
+            None => self.list(),
+        }
+    }
+
+    /// Delete bundle together with its parts no other bundle uses
+    ///
+    /// Returns false if there was no such bundle.
+    pub fn delete(&self, said: &str) -> Result<bool, Error> {
+        if let Some(bundle) = self.get(said)? {
+            for key in index_keys(&bundle) {
+                self.index.remove(key).map_err(repository_error)?;
+            }
+        }
+        self.asts.remove(said).map_err(repository_error)?;
+        self.ocafiles.remove(said).map_err(repository_error)?;
+        let record: BundleRecord = match self.bundles.remove(said).map_err(repository_error)? {
+            Some(record) => decode(said, &record)?,
+            None => return Ok(false),
+        };
+        let mut unused: Vec<String> = std::iter::once(record.capture_base)
+            .chain(record.overlays)
+            .collect();
+        for other in self.bundles.iter() {
+            let (other_said, other) = other.map_err(repository_error)?;
+            let other: BundleRecord = decode(&String::from_utf8_lossy(&other_said), &other)?;
+            unused.retain(|said| *said != other.capture_base && !other.overlays.contains(said));
+        }
+        for said in unused {
+            self.objects.remove(said).map_err(repository_error)?;
+        }
+        self.bundles.flush().map_err(repository_error)?;
+        Ok(true)
+    }
+}
+
+impl BundleResolver for Repository {
+    fn resolve(&self, said: &str) -> Result<Bundle, Error> {
+        let bundle = self
+            .get(said)?
+            .ok_or_else(|| Error::BundleNotFound(said.to_string()))?;
+        bundle.verify()?;
+        Ok(bundle)
+    }
+
+    fn resolve_ast(&self, said: &str) -> Result<Option<OCAAst>, Error> {
+        self.get_ast(said)
+    }
+}
+
+fn decode<T: for<'de> Deserialize<'de>>(said: &str, bytes: &[u8]) -> Result<T, Error> {
+    serde_json::from_slice(bytes)
+        .map_err(|e| Error::RepositoryError(format!("corrupted {}: {}", said, e)))
+}
+
+fn repository_error(e: sled::Error) -> Error {
+    Error::RepositoryError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bundle::tests::build,
+        ocafile::parse_from_string,
+        remote::{
+            tests::{bundle_with_code_table, code_table, stand_in_server},
+            RemoteRepository,
+        },
+        resolver::{DirectoryResolver, InMemoryResolver},
+    };
+    use said::derivation::SelfAddressing;
+    use std::fs;
+
+    #[test]
+    fn test_repository() {
+        let repository = Repository::temporary().unwrap();
+        let english = build("ADD ATTRIBUTE name=Text\nADD LABEL en ATTRS name=Name\n");
+        let bilingual = build(
+            "ADD ATTRIBUTE name=Text\nADD LABEL en ATTRS name=Name\nADD LABEL pl ATTRS name=\"Imię\"\n",
+        );
+        repository.store(&english).unwrap();
+        repository.store(&bilingual).unwrap();
+        repository.store(&english).unwrap();
+
+        let mut listed = repository.list().unwrap();
+        listed.sort();
+        let mut expected = vec![english.said.clone(), bilingual.said.clone()];
+        expected.sort();
+        assert_eq!(listed, expected);
+        // capture base and English label are shared
+        assert_eq!(repository.objects.len(), 3);
+
+        assert_eq!(
+            repository.get(&bilingual.said).unwrap(),
+            Some(bilingual.clone())
+        );
+        assert_eq!(repository.resolve(&english.said).unwrap(), english);
+
+        assert!(repository.delete(&bilingual.said).unwrap());
+        assert!(!repository.delete(&bilingual.said).unwrap());
+        assert_eq!(repository.get(&bilingual.said).unwrap(), None);
+        assert_eq!(repository.objects.len(), 2);
+        assert_eq!(repository.get(&english.said).unwrap(), Some(english));
+
+        let mut tampered = bilingual;
+        tampered.overlays.pop();
+        assert!(repository.store(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_fetch_from_directory() {
+        let base = build("ADD ATTRIBUTE name=Text\n");
+        let ast = parse_from_string(format!("FROM {}\nADD ATTRIBUTE age=Numeric\n", base.said));
+        let mut source = InMemoryResolver::new();
+        source.insert(base.clone());
+        let derived = Bundle::from_ast_resolved(&ast, &source).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("ocafile-fetch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for bundle in [&base, &derived] {
+            let path = dir.join(format!("{}.ocabundle", bundle.said));
+            fs::write(path, bundle.to_json().unwrap()).unwrap();
+        }
+        let ast_path = dir.join(format!("{}.ocaast", derived.said));
+        fs::write(ast_path, serde_json::to_string(&ast).unwrap()).unwrap();
+        let source = DirectoryResolver::new(&dir);
+
+        let repository = Repository::temporary().unwrap();
+        assert_eq!(
+            repository.fetch(&derived.said, &source, false).unwrap(),
+            vec![derived.said.clone()]
+        );
+        assert_eq!(repository.get_ast(&derived.said).unwrap(), Some(ast));
+        assert!(!repository.contains(&base.said).unwrap());
+        // FROM base is known from AST of the fetched bundle
+        assert_eq!(
+            repository.fetch(&derived.said, &source, true).unwrap(),
+            vec![base.said.clone()]
+        );
+        assert_eq!(repository.get(&base.said).unwrap(), Some(base));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fetch_checks_ast() {
+        let ast = parse_from_string("ADD ATTRIBUTE name=Text\n".to_string());
+        let sha2 = Bundle::from_ast_resolved_with_digest(
+            &ast,
+            &InMemoryResolver::new(),
+            &SelfAddressing::SHA2_256,
+        )
+        .unwrap();
+        let other = build("ADD ATTRIBUTE other=Text\n");
+
+        let dir = std::env::temp_dir().join(format!("ocafile-fetch-ast-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for bundle in [&sha2, &other] {
+            let path = dir.join(format!("{}.ocabundle", bundle.said));
+            fs::write(path, bundle.to_json().unwrap()).unwrap();
+            // both are given AST of the first one
+            let ast_path = dir.join(format!("{}.ocaast", bundle.said));
+            fs::write(ast_path, serde_json::to_string(&ast).unwrap()).unwrap();
+        }
+        let source = DirectoryResolver::new(&dir);
+
+        let repository = Repository::temporary().unwrap();
+        repository.fetch(&sha2.said, &source, false).unwrap();
+        repository.fetch(&other.said, &source, false).unwrap();
+        assert_eq!(repository.get_ast(&sha2.said).unwrap(), Some(ast));
+        assert_eq!(repository.get(&other.said).unwrap(), Some(other.clone()));
+        assert_eq!(repository.get_ast(&other.said).unwrap(), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fetch_from_remote() {
+        let (url, stored) = stand_in_server();
+        let remote = RemoteRepository::new(&url);
+        let code_table = code_table();
+        let bundle = bundle_with_code_table(&code_table);
+        remote.publish(&code_table).unwrap();
+        remote.publish(&bundle).unwrap();
+
+        let repository = Repository::temporary().unwrap();
+        let mut fetched = repository.fetch(&bundle.said, &remote, true).unwrap();
+        fetched.sort();
+        let mut expected = vec![bundle.said.clone(), code_table.said.clone()];
+        expected.sort();
+        assert_eq!(fetched, expected);
+        assert_eq!(repository.get(&code_table.said).unwrap(), Some(code_table));
+
+        // bundle served under other SAID is rejected
+        let other = build("ADD ATTRIBUTE other=Text\n");
+        stored
+            .lock()
+            .unwrap()
+            .insert(other.said.clone(), bundle.to_json().unwrap());
+        assert!(repository.fetch(&other.said, &remote, false).is_err());
+        assert!(!repository.contains(&other.said).unwrap());
+        assert!(matches!(
+            repository.fetch(
+                "EAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+                &remote,
+                false
+            ),
+            Err(Error::BundleNotFound(_))
+        ));
+    }
+}
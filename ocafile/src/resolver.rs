@@ -0,0 +1,267 @@
+//! Resolution of bundles referred to by SAID
+//!
+//! OCAfile starting with FROM extends an existing bundle, which is looked up
+//! by a `BundleResolver`. Resolved bundles are always verified, so a
+//! resolver never hands out a bundle with SAID other than requested.
+
+use ocaast::{
+    ast::{CommandType, Content, OCAAst},
+    state::OCAState,
+};
+use std::{collections::HashMap, fs, io, path::PathBuf};
+
+use crate::{bundle::Bundle, ocafile::error::Error};
+
+pub trait BundleResolver {
+    /// Find bundle with given SAID
+    fn resolve(&self, said: &str) -> Result<Bundle, Error>;
+
+    /// Find AST the bundle was built from, if it is known
+    fn resolve_ast(&self, _said: &str) -> Result<Option<OCAAst>, Error> {
+        Ok(None)
+    }
+}
+
+/// Resolver of bundles stored as `<said>.ocabundle` files in a directory,
+/// with their ASTs in `<said>.ocaast`, the way `ocatool build` writes them
+#[derive(Debug, Clone)]
+pub struct DirectoryResolver {
+    pub path: PathBuf,
+}
+
+impl DirectoryResolver {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        DirectoryResolver { path: path.into() }
+    }
+}
+
+impl BundleResolver for DirectoryResolver {
+    fn resolve(&self, said: &str) -> Result<Bundle, Error> {
+        let path = self.path.join(format!("{}.ocabundle", said));
+        let serialized = fs::read_to_string(&path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::BundleNotFound(said.to_string()),
+            _ => Error::BundleError(format!("can't read {}: {}", path.display(), e)),
+        })?;
+        let bundle: Bundle = serde_json::from_str(&serialized)
+            .map_err(|e| Error::BundleError(format!("invalid bundle {}: {}", said, e)))?;
+        check_resolved(said, bundle)
+    }
+
+    fn resolve_ast(&self, said: &str) -> Result<Option<OCAAst>, Error> {
+        let path = self.path.join(format!("{}.ocaast", said));
+        let serialized = match fs::read_to_string(&path) {
+            Ok(serialized) => serialized,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(Error::BundleError(format!(
+                    "can't read {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        serde_json::from_str(&serialized)
+            .map(Some)
+            .map_err(|e| Error::BundleError(format!("invalid AST of {}: {}", said, e)))
+    }
+}
+
+/// Resolver of bundles kept in memory, mostly for tests
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryResolver {
+    bundles: HashMap<String, Bundle>,
+}
+
+impl InMemoryResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, bundle: Bundle) {
+        self.bundles.insert(bundle.said.clone(), bundle);
+    }
+}
+
+impl BundleResolver for InMemoryResolver {
+    fn resolve(&self, said: &str) -> Result<Bundle, Error> {
+        match self.bundles.get(said) {
+            Some(bundle) => check_resolved(said, bundle.clone()),
+            None => Err(Error::BundleNotFound(said.to_string())),
+        }
+    }
+}
+
+/// Resolver asking other resolvers in turn, until one finds the bundle
+#[derive(Default)]
+pub struct ChainResolver<'a> {
+    resolvers: Vec<&'a dyn BundleResolver>,
+}
+
+impl<'a> ChainResolver<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, resolver: &'a dyn BundleResolver) -> Self {
+        self.resolvers.push(resolver);
+        self
+    }
+}
+
+impl BundleResolver for ChainResolver<'_> {
+    fn resolve(&self, said: &str) -> Result<Bundle, Error> {
+        for resolver in &self.resolvers {
+            match resolver.resolve(said) {
+                Err(Error::BundleNotFound(_)) => continue,
+                resolved => return resolved,
+            }
+        }
+        Err(Error::BundleNotFound(said.to_string()))
+    }
+
+    fn resolve_ast(&self, said: &str) -> Result<Option<OCAAst>, Error> {
+        for resolver in &self.resolvers {
+            if let Some(ast) = resolver.resolve_ast(said)? {
+                return Ok(Some(ast));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Check that resolved bundle is the requested one
+pub(crate) fn check_resolved(said: &str, bundle: Bundle) -> Result<Bundle, Error> {
+    if bundle.said != said {
+        return Err(Error::BundleError(format!(
+            "bundle {} found in place of {}",
+            bundle.said, said
+        )));
+    }
+    bundle.verify()?;
+    Ok(bundle)
+}
+
+/// SAID of the bundle the AST extends with FROM
+pub fn from_said(ast: &OCAAst) -> Option<&str> {
+    ast.commands
+        .iter()
+        .filter(|command| command.kind == CommandType::From)
+        .find_map(|command| match &command.content {
+            Some(Content::Bundle(content)) => Some(content.said.as_str()),
+            _ => None,
+        })
+}
+
+/// State of the bundle the AST extends with FROM, empty state without FROM
+pub fn base_state(ast: &OCAAst, resolver: &dyn BundleResolver) -> Result<OCAState, Error> {
+    match from_said(ast) {
+        Some(said) => resolver.resolve(said)?.to_state(),
+        None => Ok(OCAState::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ocafile::parse_from_string;
+    use ocaast::validator::{OCAValidator, Validator};
+    use said::derivation::SelfAddressing;
+
+    fn base_bundle() -> Bundle {
+        let ast = parse_from_string(
+            r#"
+ADD ATTRIBUTE documentNumber=Text dateOfBirth=DateTime
+ADD LABEL en ATTRS documentNumber="Document number" dateOfBirth="Date of birth"
+ADD FORMAT dateOfBirth=YYYY-MM-DD
+"#
+            .to_string(),
+        );
+        Bundle::from_ast(&ast).unwrap()
+    }
+
+    #[test]
+    fn test_build_from_resolved_bundle() {
+        let base = base_bundle();
+        let mut resolver = InMemoryResolver::new();
+        resolver.insert(base.clone());
+
+        let ast = parse_from_string(format!(
+            r#"
+FROM {}
+ADD ATTRIBUTE photo=Binary
+ADD LABEL en ATTRS photo="Photo"
+REMOVE ATTRIBUTE dateOfBirth
+"#,
+            base.said
+        ));
+        let report =
+            OCAValidator::new().validate_ast_from(&ast, base_state(&ast, &resolver).unwrap());
+        assert!(report.is_valid(), "{}", report);
+        assert!(!OCAValidator::new().validate_ast(&ast).is_valid());
+
+        let bundle = Bundle::from_ast_resolved(&ast, &resolver).unwrap();
+        assert_eq!(
+            bundle.capture_base.attributes.keys().collect::<Vec<_>>(),
+            vec!["documentNumber", "photo"]
+        );
+        assert_eq!(
+            bundle.overlays[0].content["attribute_labels"],
+            serde_json::json!({ "documentNumber": "Document number", "photo": "Photo" })
+        );
+        // format of the removed attribute is gone together with it
+        assert_eq!(bundle.overlays.len(), 1);
+
+        assert!(matches!(
+            Bundle::from_ast_resolved(&ast, &InMemoryResolver::new()),
+            Err(Error::BundleNotFound(said)) if said == base.said
+        ));
+    }
+
+    #[test]
+    fn test_resolve_bundle_with_other_digest() {
+        let base = base_bundle();
+        let base =
+            Bundle::from_state_with_digest(&base.to_state().unwrap(), &SelfAddressing::SHA2_256)
+                .unwrap();
+        let mut resolver = InMemoryResolver::new();
+        resolver.insert(base.clone());
+
+        let ast = parse_from_string(format!("FROM {}\nADD ATTRIBUTE photo=Binary\n", base.said));
+        let empty = InMemoryResolver::new();
+        let chain = ChainResolver::new().with(&empty).with(&resolver);
+        let bundle = Bundle::from_ast_resolved(&ast, &chain).unwrap();
+        assert_eq!(bundle.capture_base.attributes.len(), 3);
+        assert!(bundle.said.starts_with('E'));
+    }
+
+    #[test]
+    fn test_directory_resolver() {
+        let base = base_bundle();
+        let dir = std::env::temp_dir().join(format!("ocafile-resolver-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(format!("{}.ocabundle", base.said)),
+            serde_json::to_string_pretty(&base).unwrap(),
+        )
+        .unwrap();
+
+        let resolver = DirectoryResolver::new(&dir);
+        assert_eq!(resolver.resolve(&base.said).unwrap(), base);
+
+        let mut tampered = base.clone();
+        tampered.capture_base.flagged_attributes = vec!["documentNumber".to_string()];
+        let tampered_said = "EKYLUMmNPZeEs77Zvclf0bSN5IN-ryFULi6HbCbJcHBA";
+        fs::write(
+            dir.join(format!("{}.ocabundle", tampered_said)),
+            serde_json::to_string(&tampered).unwrap(),
+        )
+        .unwrap();
+        assert!(resolver.resolve(tampered_said).is_err());
+        assert!(matches!(
+            resolver.resolve("EAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"),
+            Err(Error::BundleNotFound(_))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
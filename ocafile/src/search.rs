@@ -0,0 +1,242 @@
+//! Search index of the local repository
+//!
+//! Index is a sled tree of `<field>\0<value>\0<said>` keys with empty
+//! values, so bundles having a value are found by a prefix scan. Indexed
+//! fields are:
+//!
+//! * `text` and `text:<lang>` - words of meta names and descriptions,
+//!   lowercased, matched by prefix
+//! * `classification` - classification code, e.g. `GICS:45102010`
+//! * `attribute` - attribute name, also as `<name>=<type>`
+
+use std::collections::BTreeSet;
+
+use crate::bundle::{overlay_type_name, Bundle};
+use ocaast::ast::OverlayType;
+
+const SEPARATOR: char = '\0';
+
+/// Meta properties which are searched as text
+const TEXT_PROPERTIES: [&str; 2] = ["name", "description"];
+
+#[derive(Debug, Default, Clone)]
+pub struct SearchQuery {
+    /// Words which must all appear in meta, as prefixes of its words
+    pub text: Option<String>,
+    /// Language of meta the text is looked for in, any if not given
+    pub lang: Option<String>,
+    pub classification: Option<String>,
+    /// Attributes bundle must have, given as `name` or `name=Type`
+    pub attributes: Vec<String>,
+}
+
+/// Prefix of index keys of field and value
+///
+/// For text the value can be a prefix of indexed word, other values are
+/// matched exactly.
+pub(crate) struct Term {
+    field: String,
+    value: String,
+    exact: bool,
+}
+
+impl Term {
+    pub(crate) fn prefix(&self) -> Vec<u8> {
+        let mut prefix = format!("{}{}{}", self.field, SEPARATOR, self.value);
+        if self.exact {
+            prefix.push(SEPARATOR);
+        }
+        prefix.into_bytes()
+    }
+}
+
+impl SearchQuery {
+    /// Terms which all must match, empty for query matching everything
+    pub(crate) fn terms(&self) -> Vec<Term> {
+        let text_field = match &self.lang {
+            Some(lang) => format!("text:{}", lang),
+            None => "text".to_string(),
+        };
+        let mut terms: Vec<Term> = self
+            .text
+            .iter()
+            .flat_map(|text| words(text))
+            .map(|word| Term {
+                field: text_field.clone(),
+                value: word,
+                exact: false,
+            })
+            .collect();
+        // language alone requires meta in that language
+        if terms.is_empty() {
+            if let Some(lang) = &self.lang {
+                terms.push(Term {
+                    field: "lang".to_string(),
+                    value: lang.clone(),
+                    exact: true,
+                });
+            }
+        }
+        if let Some(classification) = &self.classification {
+            terms.push(Term {
+                field: "classification".to_string(),
+                value: classification.clone(),
+                exact: true,
+            });
+        }
+        for attribute in &self.attributes {
+            terms.push(Term {
+                field: "attribute".to_string(),
+                value: attribute.clone(),
+                exact: true,
+            });
+        }
+        terms
+    }
+}
+
+/// SAID of bundle from index key
+pub(crate) fn key_said(key: &[u8]) -> String {
+    let key = String::from_utf8_lossy(key);
+    key.rsplit(SEPARATOR).next().unwrap_or_default().to_string()
+}
+
+/// Index keys of bundle
+pub(crate) fn index_keys(bundle: &Bundle) -> BTreeSet<Vec<u8>> {
+    let mut entries: Vec<(String, String)> = Vec::new();
+    let meta_type = overlay_type_name(&OverlayType::Meta);
+    for meta in bundle
+        .overlays
+        .iter()
+        .filter(|overlay| overlay.overlay_type == meta_type)
+    {
+        let lang = meta
+            .content
+            .get("language")
+            .and_then(|lang| lang.as_str())
+            .unwrap_or_default();
+        entries.push(("lang".to_string(), lang.to_string()));
+        for property in TEXT_PROPERTIES {
+            if let Some(text) = meta.content.get(property).and_then(|text| text.as_str()) {
+                for word in words(text) {
+                    entries.push((format!("text:{}", lang), word.clone()));
+                    entries.push(("text".to_string(), word));
+                }
+            }
+        }
+    }
+    let capture_base = &bundle.capture_base;
+    if !capture_base.classification.is_empty() {
+        entries.push((
+            "classification".to_string(),
+            capture_base.classification.clone(),
+        ));
+    }
+    for (name, attr_type) in &capture_base.attributes {
+        entries.push(("attribute".to_string(), name.clone()));
+        entries.push(("attribute".to_string(), format!("{}={}", name, attr_type)));
+    }
+
+    entries
+        .into_iter()
+        .map(|(field, value)| {
+            format!(
+                "{}{sep}{}{sep}{}",
+                field,
+                value,
+                bundle.said,
+                sep = SEPARATOR
+            )
+            .into_bytes()
+        })
+        .collect()
+}
+
+/// Lowercased words of text
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bundle::tests::build, repository::Repository};
+
+    #[test]
+    fn test_search() {
+        let repository = Repository::temporary().unwrap();
+        let passport = build(
+            r#"
+ADD CLASSIFICATION "GICS:45102010"
+ADD ATTRIBUTE documentNumber=Text dateOfBirth=DateTime
+ADD META en PROPS name="Passport" description="Travel document"
+ADD META pl PROPS name="Paszport"
+"#,
+        );
+        let visa = build(
+            r#"
+ADD ATTRIBUTE documentNumber=Text validUntil=DateTime
+ADD META en PROPS name="Visa" description="Travel permit, not a passport"
+"#,
+        );
+        repository.store(&passport).unwrap();
+        repository.store(&visa).unwrap();
+
+        let search = |query: SearchQuery| repository.search(&query).unwrap();
+        let text = |text: &str| SearchQuery {
+            text: Some(text.to_string()),
+            ..Default::default()
+        };
+
+        let mut both = vec![passport.said.clone(), visa.said.clone()];
+        both.sort();
+        assert_eq!(search(text("passport")), both);
+        assert_eq!(search(text("TRAVEL doc")), vec![passport.said.clone()]);
+        assert_eq!(search(text("paszport")), vec![passport.said.clone()]);
+        assert_eq!(
+            search(SearchQuery {
+                lang: Some("en".to_string()),
+                ..text("paszport")
+            }),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            search(SearchQuery {
+                lang: Some("pl".to_string()),
+                ..Default::default()
+            }),
+            vec![passport.said.clone()]
+        );
+        assert_eq!(
+            search(SearchQuery {
+                classification: Some("GICS:45102010".to_string()),
+                attributes: vec!["dateOfBirth".to_string()],
+                lang: Some("en".to_string()),
+                ..text("passport")
+            }),
+            vec![passport.said.clone()]
+        );
+        assert_eq!(
+            search(SearchQuery {
+                attributes: vec!["validUntil=DateTime".to_string()],
+                ..Default::default()
+            }),
+            vec![visa.said.clone()]
+        );
+        assert_eq!(search(SearchQuery::default()), both);
+        // text without words doesn't lift language condition
+        assert_eq!(
+            search(SearchQuery {
+                lang: Some("pl".to_string()),
+                ..text(" ")
+            }),
+            vec![passport.said.clone()]
+        );
+
+        repository.delete(&visa.said).unwrap();
+        assert_eq!(search(text("passport")), vec![passport.said]);
+    }
+}
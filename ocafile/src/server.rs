@@ -0,0 +1,536 @@
+//! OCA repository server
+//!
+//! Serves a `Repository` over HTTP, with the API described in
+//! `openapi.yaml`: bundles are uploaded as OCAfiles, built the way
+//! `ocatool build` does it, or as bundle JSON, and served together with
+//! their ASTs and OCAfiles by SAID. `RemoteRepository` is the client of the
+//! API.
+//!
+//! Requests are handled by `Server::handle`, independent of the HTTP
+//! server, which `Server::serve` plugs into `tiny_http`.
+
+use std::{
+    io::Read,
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+use log::{info, warn};
+use ocaast::validator::{OCAValidator, Validator};
+use said::derivation::SelfAddressing;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    bundle::Bundle,
+    decompile::{decompile_ocafile, generate_ocafile},
+    digest::digest_directive,
+    ocafile::{error::Error, try_parse_with_spans},
+    repository::Repository,
+    resolver,
+    search::SearchQuery,
+};
+
+/// OpenAPI description of the server API
+pub const OPENAPI: &str = include_str!("../openapi.yaml");
+
+/// Largest accepted request body, in bytes
+const MAX_BODY: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// Path with query, as in HTTP request line
+    pub url: String,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn new(status: u16, content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        Response {
+            status,
+            content_type,
+            body: body.into(),
+        }
+    }
+
+    fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => Response::new(status, "application/json", body),
+            Err(e) => Response::error(500, &e.to_string()),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Response::new(
+            status,
+            "application/json",
+            json!({ "error": message }).to_string(),
+        )
+    }
+
+    fn stored(status: u16, said: &str) -> Self {
+        Response::json(status, &json!({ "said": said }))
+    }
+
+    fn not_found(said: &str) -> Self {
+        Response::error(404, &Error::BundleNotFound(said.to_string()).to_string())
+    }
+}
+
+pub struct Server {
+    repository: Repository,
+}
+
+impl Server {
+    pub fn new(repository: Repository) -> Self {
+        Server { repository }
+    }
+
+    /// Answer requests coming to HTTP server, until it is shut down
+    pub fn serve(&self, http: &tiny_http::Server) {
+        for mut request in http.incoming_requests() {
+            let mut body = Vec::new();
+            let read = request
+                .as_reader()
+                .take(MAX_BODY + 1)
+                .read_to_end(&mut body);
+            let response = match read {
+                Err(e) => Response::error(400, &e.to_string()),
+                Ok(_) if body.len() as u64 > MAX_BODY => {
+                    Response::error(413, "request body is too large")
+                }
+                Ok(_) => {
+                    let request = Request {
+                        method: request.method().as_str().to_uppercase(),
+                        url: request.url().to_string(),
+                        body,
+                    };
+                    // a bug in handling of one request shouldn't take the
+                    // server down
+                    catch_unwind(AssertUnwindSafe(|| self.handle(&request)))
+                        .unwrap_or_else(|_| Response::error(500, "internal error"))
+                }
+            };
+            info!("{} {} {}", request.method(), request.url(), response.status);
+            let content_type =
+                tiny_http::Header::from_bytes(&b"Content-Type"[..], response.content_type)
+                    .expect("valid header");
+            let http_response = tiny_http::Response::from_data(response.body)
+                .with_status_code(response.status)
+                .with_header(content_type);
+            if let Err(e) = request.respond(http_response) {
+                warn!("Can't send response: {}", e);
+            }
+        }
+    }
+
+    pub fn handle(&self, request: &Request) -> Response {
+        let (path, query) = match request.url.split_once('?') {
+            Some((path, query)) => (path, query),
+            None => (request.url.as_str(), ""),
+        };
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let method = request.method.as_str();
+        let response = match (method, segments.as_slice()) {
+            ("POST", ["ocafiles"]) => self.post_ocafile(&request.body),
+            ("PUT", ["bundles", said]) => self.put_bundle(said, &request.body),
+            ("GET" | "HEAD", ["bundles", said]) => self.get_bundle(said),
+            ("GET", ["bundles", said, "ast"]) => self.get_ast(said),
+            ("GET", ["bundles", said, "ocafile"]) => self.get_ocafile(said),
+            ("GET", ["search"]) => self.search(query),
+            ("GET", ["openapi.yaml"]) => Ok(Response::new(200, "application/yaml", OPENAPI)),
+            (_, ["ocafiles"] | ["bundles", _] | ["bundles", _, "ast" | "ocafile"])
+            | (_, ["search"] | ["openapi.yaml"]) => Ok(Response::error(405, "method not allowed")),
+            _ => Ok(Response::error(404, "not found")),
+        };
+        response.unwrap_or_else(|e| Response::error(500, &e.to_string()))
+    }
+
+    fn post_ocafile(&self, body: &[u8]) -> Result<Response, Error> {
+        let ocafile = match std::str::from_utf8(body) {
+            Ok(ocafile) => ocafile,
+            Err(_) => return Ok(Response::error(400, "OCAfile is not UTF-8")),
+        };
+        let derivation = match digest_directive(ocafile) {
+            Ok(derivation) => derivation.unwrap_or(SelfAddressing::Blake3_256),
+            Err(e) => return Ok(Response::error(400, &e.to_string())),
+        };
+        let (ast, spans) = match try_parse_with_spans(ocafile.to_string()) {
+            Ok(parsed) => parsed,
+            Err(e) => return Ok(Response::error(400, &e.to_string())),
+        };
+        let mut state = match resolver::base_state(&ast, &self.repository) {
+            Ok(state) => state,
+            Err(e @ Error::BundleNotFound(_)) => {
+                return Ok(Response::error(
+                    400,
+                    &format!("Can't resolve FROM bundle: {}", e),
+                ))
+            }
+            Err(e) => return Err(e),
+        };
+        let report = OCAValidator::new()
+            .validate_ast_from(&ast, state.clone())
+            .with_spans(&spans);
+        if !report.is_valid() {
+            return Ok(Response::json(
+                400,
+                &json!({ "error": "OCAfile is invalid", "report": report }),
+            ));
+        }
+        for command in &ast.commands {
+            state.apply(command);
+        }
+        let bundle = Bundle::from_state_with_digest(&state, &derivation)?;
+        if self.repository.contains(&bundle.said)? {
+            // bundle uploaded as JSON gets the sources it was missing
+            if self.repository.get_ast(&bundle.said)?.is_none() {
+                self.repository.store_ast(&bundle.said, &ast)?;
+            }
+            if self.repository.get_ocafile(&bundle.said)?.is_none() {
+                self.repository.store_ocafile(&bundle.said, ocafile)?;
+            }
+            return Ok(Response::stored(200, &bundle.said));
+        }
+        self.repository.store(&bundle)?;
+        self.repository.store_ast(&bundle.said, &ast)?;
+        self.repository.store_ocafile(&bundle.said, ocafile)?;
+        Ok(Response::stored(201, &bundle.said))
+    }
+
+    fn put_bundle(&self, said: &str, body: &[u8]) -> Result<Response, Error> {
+        let bundle: Bundle = match serde_json::from_slice(body) {
+            Ok(bundle) => bundle,
+            Err(e) => return Ok(Response::error(400, &format!("invalid bundle: {}", e))),
+        };
+        if bundle.said != said {
+            return Ok(Response::error(
+                400,
+                &format!("bundle {} uploaded as {}", bundle.said, said),
+            ));
+        }
+        if let Err(e) = bundle.verify() {
+            return Ok(Response::error(400, &e.to_string()));
+        }
+        // verified SAID leaves no room for other content stored under it
+        match self.repository.get(said)? {
+            Some(_) => Ok(Response::stored(200, said)),
+            None => {
+                self.repository.store(&bundle)?;
+                Ok(Response::stored(201, said))
+            }
+        }
+    }
+
+    fn get_bundle(&self, said: &str) -> Result<Response, Error> {
+        match self.repository.get(said)? {
+            Some(bundle) => Ok(Response::new(
+                200,
+                "application/json",
+                bundle.to_json()?,
+            )),
+            None => Ok(Response::not_found(said)),
+        }
+    }
+
+    fn get_ast(&self, said: &str) -> Result<Response, Error> {
+        match self.repository.get_ast(said)? {
+            Some(ast) => Ok(Response::json(200, &ast)),
+            None => Ok(Response::error(
+                404,
+                &format!("AST of {} is not known", said),
+            )),
+        }
+    }
+
+    /// OCAfile as uploaded, generated from AST or decompiled from the bundle,
+    /// whichever is known first
+    ///
+    /// Bundles OCAfile can't express, e.g. with entry codes, are answered
+    /// with `422 Unprocessable Entity`.
+    fn get_ocafile(&self, said: &str) -> Result<Response, Error> {
+        if let Some(ocafile) = self.repository.get_ocafile(said)? {
+            return Ok(Response::new(200, "text/plain; charset=utf-8", ocafile));
+        }
+        let ocafile = match self.repository.get_ast(said)? {
+            Some(ast) => generate_ocafile(&ast, said),
+            None => match self.repository.get(said)? {
+                Some(bundle) => decompile_ocafile(&bundle),
+                None => return Ok(Response::not_found(said)),
+            },
+        };
+        match ocafile {
+            Ok(ocafile) => Ok(Response::new(200, "text/plain; charset=utf-8", ocafile)),
+            Err(e) => Ok(Response::error(422, &e.to_string())),
+        }
+    }
+
+    fn search(&self, query: &str) -> Result<Response, Error> {
+        let mut search = SearchQuery::default();
+        for (name, value) in parse_query(query) {
+            match name.as_str() {
+                // blank text would leave language unchecked
+                "q" => search.text = Some(value).filter(|text| !text.trim().is_empty()),
+                "lang" => search.lang = Some(value),
+                "classification" => search.classification = Some(value),
+                "has_attr" => search.attributes.push(value),
+                _ => {
+                    return Ok(Response::error(
+                        400,
+                        &format!("unknown search parameter {}", name),
+                    ))
+                }
+            }
+        }
+        Ok(Response::json(200, &self.repository.search(&search)?))
+    }
+}
+
+/// Name and value pairs of URL query, percent-decoded
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode_component(name), decode_component(value))
+        })
+        .collect()
+}
+
+fn decode_component(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = component
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match hex {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        remote::{
+            tests::{bundle_with_code_table, code_table},
+            PublishOutcome, RemoteRepository,
+        },
+        resolver::BundleResolver,
+    };
+    use std::thread;
+
+    fn request(method: &str, url: &str, body: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            url: url.to_string(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn body_json(response: &Response) -> serde_json::Value {
+        serde_json::from_slice(&response.body).unwrap()
+    }
+
+    const PASSPORT: &str = "# digest=SHA2-256\nADD ATTRIBUTE name=Text number=Text\nADD META en PROPS name=\"Passport data\"\n";
+
+    #[test]
+    fn test_handle() {
+        let server = Server::new(Repository::temporary().unwrap());
+
+        let created = server.handle(&request("POST", "/ocafiles", PASSPORT));
+        assert_eq!(created.status, 201);
+        let said = body_json(&created)["said"].as_str().unwrap().to_string();
+        assert!(said.starts_with('I'));
+        let again = server.handle(&request("POST", "/ocafiles", PASSPORT));
+        assert_eq!(
+            (again.status, body_json(&again)["said"].as_str()),
+            (200, Some(said.as_str()))
+        );
+
+        let bundle = server.handle(&request("GET", &format!("/bundles/{}", said), ""));
+        assert_eq!(bundle.status, 200);
+        let bundle: Bundle = serde_json::from_slice(&bundle.body).unwrap();
+        assert_eq!(bundle.said, said);
+        let ast = server.handle(&request("GET", &format!("/bundles/{}/ast", said), ""));
+        assert_eq!(ast.status, 200);
+        let ocafile = server.handle(&request("GET", &format!("/bundles/{}/ocafile", said), ""));
+        assert_eq!(String::from_utf8(ocafile.body).unwrap(), PASSPORT);
+
+        let derived = format!("FROM {}\nADD ATTRIBUTE issued=DateTime\n", said);
+        let derived = server.handle(&request("POST", "/ocafiles", &derived));
+        assert_eq!(derived.status, 201);
+        let derived = body_json(&derived)["said"].as_str().unwrap().to_string();
+
+        // derived bundle inherits meta and attributes of its base
+        let found = server.handle(&request(
+            "GET",
+            "/search?q=passp&has_attr=number%3DText",
+            "",
+        ));
+        let mut expected = vec![said.clone(), derived.clone()];
+        expected.sort();
+        assert_eq!(body_json(&found), json!(expected));
+        let found = server.handle(&request("GET", "/search?has_attr=issued", ""));
+        assert_eq!(body_json(&found), json!([derived]));
+        let found = server.handle(&request("GET", "/search?q=&lang=pl", ""));
+        assert_eq!(body_json(&found), json!([]));
+        let unknown = server.handle(&request("GET", "/search?name=x", ""));
+        assert_eq!(unknown.status, 400);
+
+        let invalid = server.handle(&request(
+            "POST",
+            "/ocafiles",
+            "ADD ATTRIBUTE name=Text\nADD ATTRIBUTE name=Text\n",
+        ));
+        assert_eq!(invalid.status, 400);
+        assert!(body_json(&invalid)["report"]["findings"].is_array());
+        let unparsable = server.handle(&request("POST", "/ocafiles", "ADD NOTHING\n"));
+        assert_eq!(unparsable.status, 400);
+        let unknown_base = server.handle(&request(
+            "POST",
+            "/ocafiles",
+            "FROM E2oRZ5zEKxTfTdECW-v2Q7bM_H0OD0ko7IcCwdo_u9co\n",
+        ));
+        assert_eq!(unknown_base.status, 400);
+
+        let missing = "/bundles/E2oRZ5zEKxTfTdECW-v2Q7bM_H0OD0ko7IcCwdo_u9co";
+        assert_eq!(server.handle(&request("GET", missing, "")).status, 404);
+        assert_eq!(server.handle(&request("DELETE", missing, "")).status, 405);
+        assert_eq!(server.handle(&request("GET", "/", "")).status, 404);
+        let openapi = server.handle(&request("GET", "/openapi.yaml", ""));
+        assert_eq!(openapi.body, OPENAPI.as_bytes());
+    }
+
+    #[test]
+    fn test_put_bundle() {
+        let server = Server::new(Repository::temporary().unwrap());
+        let bundle = code_table();
+        let url = format!("/bundles/{}", bundle.said);
+        let json = bundle.to_json().unwrap();
+
+        assert_eq!(server.handle(&request("PUT", &url, &json)).status, 201);
+        assert_eq!(server.handle(&request("PUT", &url, &json)).status, 200);
+        assert_eq!(server.handle(&request("HEAD", &url, "")).status, 200);
+
+        let other = bundle_with_code_table(&bundle);
+        let other_json = other.to_json().unwrap();
+        assert_eq!(
+            server.handle(&request("PUT", &url, &other_json)).status,
+            400
+        );
+        let mut tampered = other;
+        tampered.overlays.pop();
+        let url = format!("/bundles/{}", tampered.said);
+        let tampered = serde_json::to_string(&tampered).unwrap();
+        assert_eq!(server.handle(&request("PUT", &url, &tampered)).status, 400);
+
+        // bundles uploaded as JSON have OCAfile decompiled
+        let ocafile = server.handle(&request(
+            "GET",
+            &format!("/bundles/{}/ocafile", bundle.said),
+            "",
+        ));
+        assert_eq!(ocafile.status, 200);
+        assert!(String::from_utf8(ocafile.body)
+            .unwrap()
+            .contains("ADD ATTRIBUTE"));
+        assert_eq!(server.repository.get_ast(&bundle.said).unwrap(), None);
+
+        // posting its OCAfile afterwards stores the sources
+        let source = "ADD ATTRIBUTE code=Text\nADD LABEL en ATTRS code=Code\n";
+        let posted = server.handle(&request("POST", "/ocafiles", source));
+        assert_eq!(
+            (posted.status, body_json(&posted)["said"].as_str()),
+            (200, Some(bundle.said.as_str()))
+        );
+        assert!(server.repository.get_ast(&bundle.said).unwrap().is_some());
+        assert_eq!(
+            server.repository.get_ocafile(&bundle.said).unwrap(),
+            Some(source.to_string())
+        );
+
+        // OCAfile has no instruction for entry codes
+        let other = bundle_with_code_table(&bundle);
+        let url = format!("/bundles/{}", other.said);
+        let other_json = other.to_json().unwrap();
+        assert_eq!(
+            server.handle(&request("PUT", &url, &other_json)).status,
+            201
+        );
+        let ocafile = server.handle(&request("GET", &format!("{}/ocafile", url), ""));
+        assert_eq!(ocafile.status, 422);
+        assert!(body_json(&ocafile)["error"]
+            .as_str()
+            .unwrap()
+            .contains("entry_code"));
+    }
+
+    #[test]
+    fn test_serve() {
+        let http = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", http.server_addr().to_ip().unwrap());
+        thread::spawn(move || Server::new(Repository::temporary().unwrap()).serve(&http));
+
+        let built: serde_json::Value = ureq::post(&format!("{}/ocafiles", url))
+            .send_string(PASSPORT)
+            .unwrap()
+            .into_json()
+            .unwrap();
+        let said = built["said"].as_str().unwrap();
+
+        let remote = RemoteRepository::new(&url);
+        let bundle = remote.resolve(said).unwrap();
+        assert!(remote.resolve_ast(said).unwrap().is_some());
+        let code_table = code_table();
+        let mut local = crate::resolver::InMemoryResolver::new();
+        local.insert(code_table.clone());
+        let published = remote
+            .publish_with_references(&bundle_with_code_table(&code_table), &local)
+            .unwrap();
+        assert_eq!(published.len(), 2);
+        assert_eq!(
+            published[0],
+            (code_table.said.clone(), PublishOutcome::Created)
+        );
+        assert_eq!(
+            remote.publish(&bundle).unwrap(),
+            PublishOutcome::AlreadyPublished
+        );
+
+        let fetched = Repository::temporary().unwrap();
+        let saids = fetched.fetch(&published[1].0, &remote, true).unwrap();
+        assert_eq!(saids.len(), 2);
+
+        let found: Vec<String> = ureq::get(&format!("{}/search", url))
+            .query("q", "passport")
+            .call()
+            .unwrap()
+            .into_json()
+            .unwrap();
+        assert_eq!(found, vec![said.to_string()]);
+    }
+}